@@ -0,0 +1,36 @@
+// This file is part of nfl2mac.
+//
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// Generates the C header from the exported functions and types. It goes in
+// OUT_DIR, not the source tree; the tests check that the copy checked in as
+// include/nfl2mac.h still matches it.
+
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    let dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(dir.join("cbindgen.toml"))
+        .expect("cbindgen.toml should be readable");
+
+    cbindgen::generate_with_config(&dir, config)
+        .expect("the C header should generate")
+        .write_to_file(out_dir.join("nfl2mac.h"));
+}
@@ -0,0 +1,240 @@
+/* C interface to nfl2mac. Generated by cbindgen from src/lib.rs; do not edit. */
+
+#ifndef NFL2MAC_H
+#define NFL2MAC_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Which kind of entity an `Nfl2macCurve` holds.
+ */
+typedef enum Nfl2macCurveKind {
+  NFL2MAC_CURVE_KIND_LINE = 0,
+  NFL2MAC_CURVE_KIND_ARC = 1,
+} Nfl2macCurveKind;
+
+/**
+ * What a call did.
+ */
+typedef enum Nfl2macStatus {
+  NFL2MAC_STATUS_OK = 0,
+  /**
+   * A pointer that must not be null was null
+   */
+  NFL2MAC_STATUS_NULL_ARGUMENT = 1,
+  /**
+   * A string wasn't valid UTF-8
+   */
+  NFL2MAC_STATUS_INVALID_UTF8 = 2,
+  /**
+   * The NFL couldn't be parsed
+   */
+  NFL2MAC_STATUS_PARSE = 3,
+  /**
+   * A file couldn't be read or written
+   */
+  NFL2MAC_STATUS_IO = 4,
+  /**
+   * A part or entity index was past the end
+   */
+  NFL2MAC_STATUS_OUT_OF_RANGE = 5,
+  /**
+   * Something went wrong inside the library
+   */
+  NFL2MAC_STATUS_PANIC = 6,
+} Nfl2macStatus;
+
+/**
+ * An assembly, owned by the library. Free it with `nfl2mac_assembly_free`.
+ */
+typedef struct Nfl2macAssembly Nfl2macAssembly;
+
+/**
+ * One entity of a part. The start and end are filled in for both kinds;
+ * the center, radius and angles (in degrees) only for arcs.
+ */
+typedef struct Nfl2macCurve {
+  enum Nfl2macCurveKind kind;
+  double start_x;
+  double start_y;
+  double end_x;
+  double end_y;
+  double center_x;
+  double center_y;
+  double radius;
+  double go_angle;
+  double end_angle;
+} Nfl2macCurve;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * The message for the last call on this thread that failed. It stays
+ * valid until the next failing call on the same thread.
+ */
+const char *nfl2mac_last_error(void);
+
+/**
+ * Reads an NFL file, guessing its encoding.
+ *
+ * # Safety
+ *
+ * `path` must be null or a NUL-terminated string, and `out` must be null
+ * or point to writable memory for one pointer. On success, `*out` is a new
+ * assembly owned by the caller, to be freed with `nfl2mac_assembly_free`.
+ * On failure, `*out` is left alone.
+ */
+enum Nfl2macStatus nfl2mac_assembly_read(const char *path, struct Nfl2macAssembly **out);
+
+/**
+ * Reads NFL from a string.
+ *
+ * # Safety
+ *
+ * `text` must be null or a NUL-terminated string, which is only read
+ * during the call. `out` is as for `nfl2mac_assembly_read`: on success it
+ * gets a new assembly, which the caller frees with `nfl2mac_assembly_free`.
+ */
+enum Nfl2macStatus nfl2mac_assembly_from_nfl(const char *text, struct Nfl2macAssembly **out);
+
+/**
+ * Frees an assembly. Passing null does nothing.
+ *
+ * # Safety
+ *
+ * `handle` must be null or an assembly from `nfl2mac_assembly_read` or
+ * `nfl2mac_assembly_from_nfl` that hasn't been freed yet. It can't be used
+ * again afterwards.
+ */
+void nfl2mac_assembly_free(struct Nfl2macAssembly *handle);
+
+/**
+ * Splits lines wherever parts overlap, treating anything closer than the
+ * tolerance as touching. `lines_split` may be null.
+ *
+ * # Safety
+ *
+ * `handle` must be null or a live assembly, not in use by any other call
+ * at the same time, since it's changed in place. `lines_split` must be
+ * null or point to a writable `size_t`.
+ */
+enum Nfl2macStatus nfl2mac_assembly_split(struct Nfl2macAssembly *handle,
+                                          double tolerance,
+                                          size_t *lines_split);
+
+/**
+ * Counts the problems `nfl2mac check` would report.
+ *
+ * # Safety
+ *
+ * `handle` must be null or a live assembly, and `out` null or a writable
+ * `size_t`.
+ */
+enum Nfl2macStatus nfl2mac_assembly_problem_count(const struct Nfl2macAssembly *handle,
+                                                  double tolerance,
+                                                  size_t *out);
+
+/**
+ * Counts the parts. There is one per level.
+ *
+ * # Safety
+ *
+ * `handle` must be null or a live assembly, and `out` null or a writable
+ * `size_t`.
+ */
+enum Nfl2macStatus nfl2mac_assembly_part_count(const struct Nfl2macAssembly *handle, size_t *out);
+
+/**
+ * Gets the level of the part at the given index. Parts are in level order.
+ *
+ * # Safety
+ *
+ * `handle` must be null or a live assembly, and `out` null or a writable
+ * `uint64_t`.
+ */
+enum Nfl2macStatus nfl2mac_part_level(const struct Nfl2macAssembly *handle,
+                                      size_t index,
+                                      uint64_t *out);
+
+/**
+ * Counts the lines and arcs of a part.
+ *
+ * # Safety
+ *
+ * `handle` must be null or a live assembly, and `out` null or a writable
+ * `size_t`.
+ */
+enum Nfl2macStatus nfl2mac_part_curve_count(const struct Nfl2macAssembly *handle,
+                                            size_t index,
+                                            size_t *out);
+
+/**
+ * Gets one line or arc of a part, in the order they appear in NFL.
+ *
+ * # Safety
+ *
+ * `handle` must be null or a live assembly, and `out` null or a writable
+ * `Nfl2macCurve`. The curve is a copy, so nothing needs freeing.
+ */
+enum Nfl2macStatus nfl2mac_part_curve(const struct Nfl2macAssembly *handle,
+                                      size_t index,
+                                      size_t curve,
+                                      struct Nfl2macCurve *out);
+
+/**
+ * Writes the assembly as NFL into a new string, laid out like the file it
+ * was read from. Free the string with `nfl2mac_string_free`.
+ *
+ * # Safety
+ *
+ * `handle` must be null or a live assembly, and `out` null or writable
+ * memory for one pointer. The string put there belongs to the caller and
+ * must go back to `nfl2mac_string_free`, not C's `free`.
+ */
+enum Nfl2macStatus nfl2mac_assembly_to_nfl(const struct Nfl2macAssembly *handle, char **out);
+
+/**
+ * Writes the assembly as an APDL macro into a new string. Free the string
+ * with `nfl2mac_string_free`.
+ *
+ * # Safety
+ *
+ * As for `nfl2mac_assembly_to_nfl`: `handle` null or a live assembly, `out`
+ * null or writable, and the string freed only with `nfl2mac_string_free`.
+ */
+enum Nfl2macStatus nfl2mac_assembly_to_mac(const struct Nfl2macAssembly *handle, char **out);
+
+/**
+ * Writes the assembly to an NFL file, encoded like the one it was read
+ * from. An existing file is overwritten.
+ *
+ * # Safety
+ *
+ * `handle` must be null or a live assembly, and `path` null or a
+ * NUL-terminated string. Neither is kept after the call.
+ */
+enum Nfl2macStatus nfl2mac_assembly_write_nfl(const struct Nfl2macAssembly *handle,
+                                              const char *path);
+
+/**
+ * Frees a string handed out by this library. Passing null does nothing.
+ *
+ * # Safety
+ *
+ * `s` must be null or a string from `nfl2mac_assembly_to_nfl` or
+ * `nfl2mac_assembly_to_mac` that hasn't been freed yet. Strings from
+ * anywhere else, including `nfl2mac_last_error`, must not be passed.
+ */
+void nfl2mac_string_free(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NFL2MAC_H */
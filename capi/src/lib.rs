@@ -0,0 +1,489 @@
+// This file is part of nfl2mac.
+//
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+//! C interface to nfl2mac. The header, `include/nfl2mac.h`, is generated
+//! from this file when the crate is built.
+//!
+//! Every function returns a status code. When it isn't `OK`, a message
+//! saying what went wrong can be had from `nfl2mac_last_error`. Panics are
+//! caught and come back as `PANIC` rather than unwinding into C.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+
+use nfl2mac::{Assembly, Curve, ReadError};
+
+/// What a call did.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nfl2macStatus {
+    Ok = 0,
+    /// A pointer that must not be null was null
+    NullArgument = 1,
+    /// A string wasn't valid UTF-8
+    InvalidUtf8 = 2,
+    /// The NFL couldn't be parsed
+    Parse = 3,
+    /// A file couldn't be read or written
+    Io = 4,
+    /// A part or entity index was past the end
+    OutOfRange = 5,
+    /// Something went wrong inside the library
+    Panic = 6,
+}
+
+/// Which kind of entity an `Nfl2macCurve` holds.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nfl2macCurveKind {
+    Line = 0,
+    Arc = 1,
+}
+
+/// One entity of a part. The start and end are filled in for both kinds;
+/// the center, radius and angles (in degrees) only for arcs.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Nfl2macCurve {
+    pub kind: Nfl2macCurveKind,
+    pub start_x: f64,
+    pub start_y: f64,
+    pub end_x: f64,
+    pub end_y: f64,
+    pub center_x: f64,
+    pub center_y: f64,
+    pub radius: f64,
+    pub go_angle: f64,
+    pub end_angle: f64,
+}
+
+/// An assembly, owned by the library. Free it with `nfl2mac_assembly_free`.
+pub struct Nfl2macAssembly {
+    inner: Assembly,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+struct Error(Nfl2macStatus, String);
+
+impl From<ReadError> for Error {
+    fn from(e: ReadError) -> Error {
+        match e {
+            ReadError::Io(e) => Error(Nfl2macStatus::Io, e.to_string()),
+            ReadError::Parse(message) => Error(Nfl2macStatus::Parse, message),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error(Nfl2macStatus::Io, e.to_string())
+    }
+}
+
+/// Runs the body, turning errors and panics into a status and message.
+fn guard<F>(body: F) -> Nfl2macStatus
+where F: FnOnce() -> Result<(), Error>
+{
+    let Error(status, message) = match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => return Nfl2macStatus::Ok,
+        Ok(Err(e)) => e,
+        Err(_) => Error(Nfl2macStatus::Panic, String::from("nfl2mac panicked")),
+    };
+
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = message);
+    status
+}
+
+fn null() -> Error {
+    Error(Nfl2macStatus::NullArgument, String::from("Unexpected null pointer"))
+}
+
+unsafe fn string<'a>(s: *const c_char) -> Result<&'a str, Error> {
+    if s.is_null() {
+        return Err(null());
+    }
+
+    CStr::from_ptr(s).to_str()
+        .map_err(|e| Error(Nfl2macStatus::InvalidUtf8, e.to_string()))
+}
+
+unsafe fn assembly<'a>(handle: *const Nfl2macAssembly) -> Result<&'a Assembly, Error> {
+    handle.as_ref().map(|a| &a.inner).ok_or_else(null)
+}
+
+unsafe fn assembly_mut<'a>(handle: *mut Nfl2macAssembly) -> Result<&'a mut Assembly, Error> {
+    handle.as_mut().map(|a| &mut a.inner).ok_or_else(null)
+}
+
+unsafe fn put<T>(out: *mut T, value: T) -> Result<(), Error> {
+    if out.is_null() {
+        return Err(null());
+    }
+
+    *out = value;
+    Ok(())
+}
+
+fn part(asm: &Assembly, part: usize) -> Result<&nfl2mac::Part, Error> {
+    asm.parts.get(part).ok_or_else(|| Error(
+        Nfl2macStatus::OutOfRange,
+        format!("Part {} is out of range; there are {}", part, asm.parts.len())
+    ))
+}
+
+/// Null bytes can only get into the output from the input, such as in a
+/// label, so they count as something wrong with what was read.
+fn text(s: String) -> Result<*mut c_char, Error> {
+    CString::new(s).map(CString::into_raw)
+        .map_err(|_| Error(Nfl2macStatus::Parse, String::from("The input has a null byte, which C strings can't hold")))
+}
+
+/// The message for the last call on this thread that failed. It stays
+/// valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn nfl2mac_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
+
+/// Reads an NFL file, guessing its encoding.
+///
+/// # Safety
+///
+/// `path` must be null or a NUL-terminated string, and `out` must be null
+/// or point to writable memory for one pointer. On success, `*out` is a new
+/// assembly owned by the caller, to be freed with `nfl2mac_assembly_free`.
+/// On failure, `*out` is left alone.
+#[no_mangle]
+pub unsafe extern "C" fn nfl2mac_assembly_read(path: *const c_char, out: *mut *mut Nfl2macAssembly) -> Nfl2macStatus {
+    guard(|| {
+        let inner = Assembly::read(string(path)?, None)?;
+        put(out, Box::into_raw(Box::new(Nfl2macAssembly { inner })))
+    })
+}
+
+/// Reads NFL from a string.
+///
+/// # Safety
+///
+/// `text` must be null or a NUL-terminated string, which is only read
+/// during the call. `out` is as for `nfl2mac_assembly_read`: on success it
+/// gets a new assembly, which the caller frees with `nfl2mac_assembly_free`.
+#[no_mangle]
+pub unsafe extern "C" fn nfl2mac_assembly_from_nfl(text: *const c_char, out: *mut *mut Nfl2macAssembly) -> Nfl2macStatus {
+    guard(|| {
+        let inner = Assembly::from_nfl(string(text)?)?;
+        put(out, Box::into_raw(Box::new(Nfl2macAssembly { inner })))
+    })
+}
+
+/// Frees an assembly. Passing null does nothing.
+///
+/// # Safety
+///
+/// `handle` must be null or an assembly from `nfl2mac_assembly_read` or
+/// `nfl2mac_assembly_from_nfl` that hasn't been freed yet. It can't be used
+/// again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn nfl2mac_assembly_free(handle: *mut Nfl2macAssembly) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Splits lines wherever parts overlap, treating anything closer than the
+/// tolerance as touching. `lines_split` may be null.
+///
+/// # Safety
+///
+/// `handle` must be null or a live assembly, not in use by any other call
+/// at the same time, since it's changed in place. `lines_split` must be
+/// null or point to a writable `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn nfl2mac_assembly_split(handle: *mut Nfl2macAssembly, tolerance: f64, lines_split: *mut usize) -> Nfl2macStatus {
+    guard(|| {
+        let report = assembly_mut(handle)?.split(tolerance);
+        if !lines_split.is_null() {
+            *lines_split = report.totals.lines_split;
+        }
+        Ok(())
+    })
+}
+
+/// Counts the problems `nfl2mac check` would report.
+///
+/// # Safety
+///
+/// `handle` must be null or a live assembly, and `out` null or a writable
+/// `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn nfl2mac_assembly_problem_count(handle: *const Nfl2macAssembly, tolerance: f64, out: *mut usize) -> Nfl2macStatus {
+    guard(|| put(out, assembly(handle)?.validate(tolerance).len()))
+}
+
+/// Counts the parts. There is one per level.
+///
+/// # Safety
+///
+/// `handle` must be null or a live assembly, and `out` null or a writable
+/// `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn nfl2mac_assembly_part_count(handle: *const Nfl2macAssembly, out: *mut usize) -> Nfl2macStatus {
+    guard(|| put(out, assembly(handle)?.parts.len()))
+}
+
+/// Gets the level of the part at the given index. Parts are in level order.
+///
+/// # Safety
+///
+/// `handle` must be null or a live assembly, and `out` null or a writable
+/// `uint64_t`.
+#[no_mangle]
+pub unsafe extern "C" fn nfl2mac_part_level(handle: *const Nfl2macAssembly, index: usize, out: *mut u64) -> Nfl2macStatus {
+    guard(|| put(out, part(assembly(handle)?, index)?.level))
+}
+
+/// Counts the lines and arcs of a part.
+///
+/// # Safety
+///
+/// `handle` must be null or a live assembly, and `out` null or a writable
+/// `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn nfl2mac_part_curve_count(handle: *const Nfl2macAssembly, index: usize, out: *mut usize) -> Nfl2macStatus {
+    guard(|| put(out, part(assembly(handle)?, index)?.curves.len()))
+}
+
+/// Gets one line or arc of a part, in the order they appear in NFL.
+///
+/// # Safety
+///
+/// `handle` must be null or a live assembly, and `out` null or a writable
+/// `Nfl2macCurve`. The curve is a copy, so nothing needs freeing.
+#[no_mangle]
+pub unsafe extern "C" fn nfl2mac_part_curve(handle: *const Nfl2macAssembly, index: usize, curve: usize, out: *mut Nfl2macCurve) -> Nfl2macStatus {
+    guard(|| {
+        let p = part(assembly(handle)?, index)?;
+        let c = p.curves.get(curve).ok_or_else(|| Error(
+            Nfl2macStatus::OutOfRange,
+            format!("Curve {} of LEVEL/{} is out of range; there are {}", curve, p.level, p.curves.len())
+        ))?;
+
+        let (start, end) = (c.start(), c.end());
+        let mut result = Nfl2macCurve {
+            kind: Nfl2macCurveKind::Line,
+            start_x: start.x, start_y: start.y,
+            end_x: end.x, end_y: end.y,
+            center_x: 0., center_y: 0., radius: 0., go_angle: 0., end_angle: 0.,
+        };
+
+        if let Curve::Arc(a) = c {
+            result.kind = Nfl2macCurveKind::Arc;
+            result.center_x = a.center.x;
+            result.center_y = a.center.y;
+            result.radius = a.radius;
+            result.go_angle = a.go_angle;
+            result.end_angle = a.end_angle;
+        }
+
+        put(out, result)
+    })
+}
+
+/// Writes the assembly as NFL into a new string, laid out like the file it
+/// was read from. Free the string with `nfl2mac_string_free`.
+///
+/// # Safety
+///
+/// `handle` must be null or a live assembly, and `out` null or writable
+/// memory for one pointer. The string put there belongs to the caller and
+/// must go back to `nfl2mac_string_free`, not C's `free`.
+#[no_mangle]
+pub unsafe extern "C" fn nfl2mac_assembly_to_nfl(handle: *const Nfl2macAssembly, out: *mut *mut c_char) -> Nfl2macStatus {
+    guard(|| {
+        let asm = assembly(handle)?;
+        put(out, text(asm.to_nfl(&asm.write_options()))?)
+    })
+}
+
+/// Writes the assembly as an APDL macro into a new string. Free the string
+/// with `nfl2mac_string_free`.
+///
+/// # Safety
+///
+/// As for `nfl2mac_assembly_to_nfl`: `handle` null or a live assembly, `out`
+/// null or writable, and the string freed only with `nfl2mac_string_free`.
+#[no_mangle]
+pub unsafe extern "C" fn nfl2mac_assembly_to_mac(handle: *const Nfl2macAssembly, out: *mut *mut c_char) -> Nfl2macStatus {
+    guard(|| {
+        let asm = assembly(handle)?;
+        put(out, text(asm.to_mac(&asm.write_options()))?)
+    })
+}
+
+/// Writes the assembly to an NFL file, encoded like the one it was read
+/// from. An existing file is overwritten.
+///
+/// # Safety
+///
+/// `handle` must be null or a live assembly, and `path` null or a
+/// NUL-terminated string. Neither is kept after the call.
+#[no_mangle]
+pub unsafe extern "C" fn nfl2mac_assembly_write_nfl(handle: *const Nfl2macAssembly, path: *const c_char) -> Nfl2macStatus {
+    guard(|| {
+        let asm = assembly(handle)?;
+        fs::write(string(path)?, asm.to_bytes(&asm.write_options()))?;
+        Ok(())
+    })
+}
+
+/// Frees a string handed out by this library. Passing null does nothing.
+///
+/// # Safety
+///
+/// `s` must be null or a string from `nfl2mac_assembly_to_nfl` or
+/// `nfl2mac_assembly_to_mac` that hasn't been freed yet. Strings from
+/// anywhere else, including `nfl2mac_last_error`, must not be passed.
+#[no_mangle]
+pub unsafe extern "C" fn nfl2mac_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    const SAMPLE: &[u8] = concat!(env!("CARGO_MANIFEST_DIR"), "/../sample/test_assy.nfl\0").as_bytes();
+
+    fn c(s: &[u8]) -> *const c_char {
+        s.as_ptr() as *const c_char
+    }
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(nfl2mac_last_error()) }.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn handles() {
+        unsafe {
+            let mut asm = ptr::null_mut();
+            assert_eq!(nfl2mac_assembly_read(c(SAMPLE), &mut asm), Nfl2macStatus::Ok);
+            assert!(!asm.is_null());
+
+            // The count is optional
+            assert_eq!(nfl2mac_assembly_split(asm, 0.000001, ptr::null_mut()), Nfl2macStatus::Ok);
+
+            let mut problems = usize::MAX;
+            assert_eq!(nfl2mac_assembly_problem_count(asm, 0.000001, &mut problems), Nfl2macStatus::Ok);
+            assert_eq!(problems, 0);
+
+            // Each call hands out a new string
+            let (mut a, mut b) = (ptr::null_mut(), ptr::null_mut());
+            assert_eq!(nfl2mac_assembly_to_mac(asm, &mut a), Nfl2macStatus::Ok);
+            assert_eq!(nfl2mac_assembly_to_mac(asm, &mut b), Nfl2macStatus::Ok);
+            assert_ne!(a, b);
+            assert!(CStr::from_ptr(a).to_str().unwrap().contains("/PREP7"));
+
+            nfl2mac_string_free(a);
+            nfl2mac_string_free(b);
+            nfl2mac_assembly_free(asm);
+
+            nfl2mac_string_free(ptr::null_mut());
+            nfl2mac_assembly_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn curve_fields() {
+        let nfl = b"LEVEL/3\nC00001=CIRCLE/CENTER,1,1,RADIUS,2,GOANG,0,ENDANG,90\nL00002=LINE/1,3,1,5\n\0";
+
+        unsafe {
+            let mut asm = ptr::null_mut();
+            assert_eq!(nfl2mac_assembly_from_nfl(c(nfl), &mut asm), Nfl2macStatus::Ok);
+
+            let mut curve = std::mem::zeroed::<Nfl2macCurve>();
+            assert_eq!(nfl2mac_part_curve(asm, 0, 0, &mut curve), Nfl2macStatus::Ok);
+            assert_eq!(curve.kind, Nfl2macCurveKind::Arc);
+            assert_eq!((curve.start_x, curve.start_y), (3., 1.));
+            assert_eq!((curve.center_x, curve.center_y, curve.radius), (1., 1., 2.));
+
+            // Whatever was in the struct before gets overwritten, zeros and all
+            assert_eq!(nfl2mac_part_curve(asm, 0, 1, &mut curve), Nfl2macStatus::Ok);
+            assert_eq!(curve.kind, Nfl2macCurveKind::Line);
+            assert_eq!((curve.end_x, curve.end_y), (1., 5.));
+            assert_eq!((curve.center_x, curve.radius, curve.end_angle), (0., 0., 0.));
+
+            nfl2mac_assembly_free(asm);
+        }
+    }
+
+    #[test]
+    fn statuses() {
+        unsafe {
+            let mut asm = ptr::null_mut();
+            let mut count = 0;
+
+            assert_eq!(nfl2mac_assembly_from_nfl(c(b"LEVEL/1\n\0"), ptr::null_mut()), Nfl2macStatus::NullArgument);
+            assert_eq!(nfl2mac_assembly_part_count(ptr::null(), &mut count), Nfl2macStatus::NullArgument);
+            assert_eq!(nfl2mac_assembly_from_nfl(c(b"LEVEL/\xff\n\0"), &mut asm), Nfl2macStatus::InvalidUtf8);
+            assert_eq!(nfl2mac_assembly_read(c(b"no such file.nfl\0"), &mut asm), Nfl2macStatus::Io);
+
+            // Failures leave the out pointer alone
+            let bad = b"LEVEL/1\nC00001=CIRCLE/CENTER,0,0,RADIUS,r\n\0";
+            assert_eq!(nfl2mac_assembly_from_nfl(c(bad), &mut asm), Nfl2macStatus::Parse);
+            assert!(asm.is_null());
+            let parse_error = last_error();
+            assert!(!parse_error.is_empty());
+
+            // ...and the message lasts until the next failure, whatever succeeds in between
+            assert_eq!(nfl2mac_assembly_read(c(SAMPLE), &mut asm), Nfl2macStatus::Ok);
+            assert_eq!(last_error(), parse_error);
+
+            assert_eq!(nfl2mac_part_curve_count(asm, 999, &mut count), Nfl2macStatus::OutOfRange);
+            assert!(last_error().contains("999"));
+            nfl2mac_assembly_free(asm);
+
+            // C strings can't carry a null byte read from a file
+            let file = std::env::temp_dir().join("nfl2mac-capi-null.nfl");
+            fs::write(&file, "LEVEL/1\nCOLOR/GR\0EEN\nL00001=LINE/0,0,1,0\n").unwrap();
+            let path = CString::new(file.to_str().unwrap()).unwrap();
+            assert_eq!(nfl2mac_assembly_read(path.as_ptr(), &mut asm), Nfl2macStatus::Ok);
+            fs::remove_file(&file).unwrap();
+
+            let mut nfl = ptr::null_mut();
+            assert_eq!(nfl2mac_assembly_to_nfl(asm, &mut nfl), Nfl2macStatus::Parse);
+            assert!(nfl.is_null());
+            nfl2mac_assembly_free(asm);
+        }
+    }
+
+    #[test]
+    fn header_up_to_date() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/nfl2mac.h"));
+        let checked_in = include_str!("../include/nfl2mac.h");
+
+        assert!(generated == checked_in,
+                "include/nfl2mac.h is out of date; regenerate it as the README says");
+    }
+}
@@ -0,0 +1,217 @@
+// This file is part of nfl2mac.
+//
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The `nfl2mac` Python module. Assemblies stay on the Rust side; parts,
+//! lines and arcs are handed out as plain copies.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use euclid::Point2D;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use nfl2mac::{Assembly as Inner, ReadError, TextEncoding, TextFormat};
+
+type Point = (f64, f64);
+
+fn point(p: Point2D<f64, f64>) -> Point {
+    (p.x, p.y)
+}
+
+fn read_error(e: ReadError) -> PyErr {
+    match e {
+        ReadError::Io(e) => e.into(),
+        ReadError::Parse(message) => PyValueError::new_err(message),
+    }
+}
+
+/// A cross-section read from an NFL file.
+#[pyclass(module = "nfl2mac")]
+struct Assembly {
+    inner: Inner,
+}
+
+#[pymethods]
+impl Assembly {
+    /// An assembly with no parts.
+    #[new]
+    fn new() -> Assembly {
+        Assembly { inner: Inner::empty() }
+    }
+
+    /// Reads an NFL file. The encoding ("utf-8", "windows-1252" or
+    /// "latin-1") is guessed when not given.
+    #[staticmethod]
+    #[pyo3(signature = (path, encoding = None))]
+    fn read(path: PathBuf, encoding: Option<&str>) -> PyResult<Assembly> {
+        let encoding = match encoding {
+            Some(e) => Some(e.parse::<TextEncoding>().map_err(|_| {
+                PyValueError::new_err(format!("Unknown encoding {:?}", e))
+            })?),
+            None => None,
+        };
+
+        let inner = Inner::read(&path, encoding).map_err(read_error)?;
+        Ok(Assembly { inner })
+    }
+
+    /// Reads NFL from a string.
+    #[staticmethod]
+    fn from_nfl(text: &str) -> PyResult<Assembly> {
+        let inner = Inner::from_nfl(text).map_err(read_error)?;
+        Ok(Assembly { inner })
+    }
+
+    /// Splits lines wherever parts overlap, treating anything closer than
+    /// the tolerance as touching. Returns the totals of what was done.
+    fn split<'py>(&mut self, py: Python<'py>, tolerance: f64) -> PyResult<Bound<'py, PyDict>> {
+        let totals = self.inner.split(tolerance).totals;
+
+        let result = PyDict::new(py);
+        result.set_item("pairs", totals.pairs)?;
+        result.set_item("overlaps", totals.overlaps)?;
+        result.set_item("lines_split", totals.lines_split)?;
+        result.set_item("split_points", totals.split_points)?;
+        result.set_item("pieces", totals.pieces)?;
+
+        Ok(result)
+    }
+
+    /// Descriptions of everything wrong with the geometry; empty if it's
+    /// ready to be meshed.
+    fn validate(&self, tolerance: f64) -> Vec<String> {
+        self.inner.validate(tolerance).iter().map(ToString::to_string).collect()
+    }
+
+    /// Copies of the parts, in level order.
+    #[getter]
+    fn parts(&self) -> Vec<Part> {
+        self.inner.parts.iter().map(Part::new).collect()
+    }
+
+    /// The assembly as NFL, laid out like the file it was read from.
+    fn to_nfl(&self) -> String {
+        self.inner.to_nfl(&self.inner.write_options())
+    }
+
+    /// The assembly as an ANSYS APDL macro.
+    fn to_mac(&self) -> String {
+        self.inner.to_mac(&self.inner.write_options())
+    }
+
+    /// Writes the assembly to an NFL file, encoded like the one it was
+    /// read from.
+    fn write_nfl(&self, path: PathBuf) -> PyResult<()> {
+        let opts = self.inner.write_options();
+        fs::write(path, self.inner.to_bytes(&opts))?;
+        Ok(())
+    }
+
+    /// Writes the assembly to an APDL macro file.
+    fn write_mac(&self, path: PathBuf) -> PyResult<()> {
+        let opts = self.inner.write_options();
+        let format = TextFormat { eof_marker: false, ..opts.text.clone() };
+        fs::write(path, format.encode(&self.inner.to_mac(&opts)))?;
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Assembly with {} parts>", self.inner.parts.len())
+    }
+}
+
+/// A copy of one level of an assembly.
+#[pyclass(module = "nfl2mac", get_all, frozen)]
+#[derive(Clone)]
+struct Part {
+    level: u64,
+    attrs: BTreeMap<String, String>,
+    lines: Vec<Line>,
+    arcs: Vec<Arc>,
+}
+
+impl Part {
+    fn new(p: &nfl2mac::Part) -> Part {
+        Part {
+            level: p.level,
+            attrs: p.data.clone(),
+            lines: p.lines().map(|l| Line {
+                p1: point(l.p1()),
+                p2: point(l.p2()),
+                label: l.label().map(String::from),
+            }).collect(),
+            arcs: p.arcs().map(|a| Arc {
+                center: point(a.center),
+                radius: a.radius,
+                go_angle: a.go_angle,
+                end_angle: a.end_angle,
+                label: a.label.clone(),
+            }).collect(),
+        }
+    }
+}
+
+#[pymethods]
+impl Part {
+    fn __repr__(&self) -> String {
+        format!("<Part LEVEL/{} with {} lines and {} arcs>",
+                self.level, self.lines.len(), self.arcs.len())
+    }
+}
+
+#[pyclass(module = "nfl2mac", get_all, frozen)]
+#[derive(Clone)]
+struct Line {
+    p1: Point,
+    p2: Point,
+    label: Option<String>,
+}
+
+#[pymethods]
+impl Line {
+    fn __repr__(&self) -> String {
+        format!("Line({:?}, {:?})", self.p1, self.p2)
+    }
+}
+
+#[pyclass(module = "nfl2mac", get_all, frozen)]
+#[derive(Clone)]
+struct Arc {
+    center: Point,
+    radius: f64,
+    go_angle: f64,
+    end_angle: f64,
+    label: Option<String>,
+}
+
+#[pymethods]
+impl Arc {
+    fn __repr__(&self) -> String {
+        format!("Arc({:?}, r={}, {}..{})", self.center, self.radius, self.go_angle, self.end_angle)
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "nfl2mac")]
+fn nfl2mac_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Assembly>()?;
+    m.add_class::<Part>()?;
+    m.add_class::<Line>()?;
+    m.add_class::<Arc>()?;
+    Ok(())
+}
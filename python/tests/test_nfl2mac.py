@@ -0,0 +1,89 @@
+# This file is part of nfl2mac.
+#
+# nfl2mac is free software: you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation, either version 3 of the License, or
+# (at your option) any later version.
+#
+# nfl2mac is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+import os
+import pathlib
+import tempfile
+import unittest
+
+import nfl2mac
+
+SAMPLE = os.path.join(os.path.dirname(__file__), "..", "..", "sample", "test_assy.nfl")
+
+
+class AssemblyTest(unittest.TestCase):
+    def test_read(self):
+        asm = nfl2mac.Assembly.read(pathlib.Path(SAMPLE))
+        parts = {p.level: p for p in asm.parts}
+
+        self.assertEqual(parts[1].attrs, {"FONT": "SOLID", "COLOR": "GREEN"})
+        self.assertEqual(parts[1].lines[0].label, "L00001")
+        self.assertEqual(parts[1].lines[0].p2, (0.7577722283114, 0.4375))
+
+        (arc,) = parts[2].arcs
+        self.assertEqual((arc.go_angle, arc.end_angle), (120.0, 270.0))
+        (circle,) = parts[4].arcs
+        self.assertEqual((circle.radius, circle.go_angle, circle.end_angle), (0.27, 0.0, 360.0))
+
+    def test_copies(self):
+        asm = nfl2mac.Assembly.read(SAMPLE)
+        before = asm.parts
+
+        totals = asm.split(1e-6)
+        self.assertGreater(totals["lines_split"], 0)
+
+        # Parts taken earlier don't see the split
+        after = asm.parts
+        self.assertLess(sum(len(p.lines) for p in before), sum(len(p.lines) for p in after))
+
+        # ...and changing a copy doesn't reach back into the assembly
+        after[0].attrs["COLOR"] = "RED"
+        self.assertEqual(asm.parts[0].attrs["COLOR"], "GREEN")
+
+        with self.assertRaises(AttributeError):
+            after[0].level = 99
+
+    def test_write(self):
+        asm = nfl2mac.Assembly.read(SAMPLE, encoding="latin-1")
+        asm.split(1e-6)
+
+        with tempfile.TemporaryDirectory() as d:
+            asm.write_nfl(os.path.join(d, "out.nfl"))
+            asm.write_mac(os.path.join(d, "out.mac"))
+            again = nfl2mac.Assembly.read(os.path.join(d, "out.nfl"))
+
+            with open(os.path.join(d, "out.mac")) as f:
+                self.assertEqual(f.read(), asm.to_mac())
+
+        self.assertEqual(again.validate(1e-6), [])
+        self.assertEqual(repr(again), repr(asm))
+
+    def test_errors(self):
+        # Parse errors say where they are
+        with self.assertRaisesRegex(ValueError, "LEVEL/one"):
+            nfl2mac.Assembly.from_nfl("LEVEL/one\n")
+
+        with self.assertRaises(FileNotFoundError):
+            nfl2mac.Assembly.read("no such file.nfl")
+
+        with self.assertRaises(OSError):
+            nfl2mac.Assembly().write_nfl(os.path.join(SAMPLE, "inside a file.nfl"))
+
+        with self.assertRaisesRegex(ValueError, "ebcdic"):
+            nfl2mac.Assembly.read(SAMPLE, encoding="ebcdic")
+
+
+if __name__ == "__main__":
+    unittest.main()
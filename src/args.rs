@@ -17,89 +17,786 @@
 // https://doc.rust-lang.org/book/ch12-03-improving-error-handling-and-modularity.html
 
 use structopt::StructOpt;
-use std::path::PathBuf;
+use structopt::clap::ArgMatches;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use glob::{Pattern, MatchOptions};
+use log::{debug, warn};
 
-use strum_macros::EnumString;
+use strum_macros::{EnumString, Display};
 
+use nfl2mac::assembly::{Assembly, Margin};
+use nfl2mac::assembly::levels::{LevelSet, AttrMatch, Remap, LevelFilter};
+use nfl2mac::assembly::dxf::LayerLevel;
+use crate::project::{self, Project};
+use crate::diag::{self, Failure, Status};
+use nfl2mac::assembly::nfl::{WriteOptions, NumberFormat, LabelScheme};
+use nfl2mac::assembly::encoding::{TextFormat, TextEncoding, LineEnding};
 
-const NFL_EXTENSION: &str = ".nfl";
-const SPLIT_SUFFIX: &str = "-split";
+
+const NFL_EXTENSION: &str = "nfl";
+const DXF_EXTENSION: &str = "dxf";
+const MAC_EXTENSION: &str = "mac";
+const JSON_EXTENSION: &str = "json";
+const YAML_EXTENSION: &str = "yaml";
+const YML_EXTENSION: &str = "yml";
+const SVG_EXTENSION: &str = "svg";
+const GGB_EXTENSION: &str = "ggb";
+const STDOUT_NAME: &str = "-";
+
+// How much of the file we look at when working out what it is
+const SNIFF_LEN: u64 = 4096;
 
 #[derive(Debug)]
-#[derive(EnumString)]
+#[derive(EnumString, Display)]
 #[derive(Clone)]
 pub enum FileType {
 	RawNFL,
 	SplitNFL,
+	RawDXF,
+}
+
+impl FileType {
+    /// How files of this type are read.
+    pub fn input_format(&self) -> InputFormat {
+        match self {
+            FileType::RawNFL | FileType::SplitNFL => InputFormat::Nfl,
+            FileType::RawDXF => InputFormat::Dxf,
+        }
+    }
+
+    /// Works out what kind of file we've been given by looking at what's in
+    /// it. The extension is only used if the contents don't give it away.
+    ///
+    /// NFL files are read with `read` to check whether they still have
+    /// overlaps that need splitting; if they don't, they're considered
+    /// already split.
+    pub fn detect<R, M>(path: &Path, read: R, line_precision: M) -> Result<FileType, Failure>
+    where R: FnOnce(&Path) -> Result<Assembly, Failure>, M: Margin
+    {
+        let mut head = Vec::new();
+        File::open(path)
+            .and_then(|f| f.take(SNIFF_LEN).read_to_end(&mut head))
+            .map_err(|e| Failure::new(Status::Io, format!("Could not read {:?}: {}", path, e)))?;
+
+        let head = String::from_utf8_lossy(&head);
+        let mut lines = head.lines().map(str::trim).filter(|l| !l.is_empty());
+
+        let is_nfl = head.lines().any(|l| {
+            l.starts_with("PARTNO/") || l.starts_with("LEVEL/")
+                || l.contains("=LINE/") || l.contains("=CIRCLE/")
+        });
+
+        // ASCII DXF files are pairs of lines, starting with a group code of
+        // 0 and a SECTION. Binary ones have a sentinel instead.
+        let is_dxf = head.starts_with("AutoCAD Binary DXF")
+            || (lines.next() == Some("0") && lines.next() == Some("SECTION"));
+
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("");
+
+        if is_dxf || (!is_nfl && extension.eq_ignore_ascii_case(DXF_EXTENSION)) {
+            Ok(FileType::RawDXF)
+        }
+        else if is_nfl || extension.eq_ignore_ascii_case(NFL_EXTENSION) {
+            if read(path)?.is_conforming(line_precision) {
+                Ok(FileType::SplitNFL)
+            }
+            else {
+                Ok(FileType::RawNFL)
+            }
+        }
+        else {
+            Err(Failure::new(Status::Parse, format!("Could not work out what kind of file {:?} is", path)))
+        }
+    }
+}
+
+
+#[derive(Debug)]
+#[derive(EnumString, Display)]
+#[derive(Clone, Copy, PartialEq)]
+#[strum(serialize_all = "kebab_case")]
+pub enum OutputFormat {
+	/// NFL, the same as what gets read in
+	Nfl,
+	/// ANSYS APDL macro
+	Mac,
+	/// The whole model as JSON
+	Json,
+	/// The whole model as YAML
+	Yaml,
+	/// ASCII DXF, with a layer per part
+	Dxf,
+	/// A drawing of the parts, for looking at in a browser
+	Svg,
+	/// A GeoGebra construction
+	Ggb,
+}
+
+impl OutputFormat {
+	pub fn extension(&self) -> &'static str {
+		match self {
+			OutputFormat::Nfl => NFL_EXTENSION,
+			OutputFormat::Mac => MAC_EXTENSION,
+			OutputFormat::Json => JSON_EXTENSION,
+			OutputFormat::Yaml => YAML_EXTENSION,
+			OutputFormat::Dxf => DXF_EXTENSION,
+			OutputFormat::Svg => SVG_EXTENSION,
+			OutputFormat::Ggb => GGB_EXTENSION,
+		}
+	}
+}
+
+/// What convert reads its input as.
+#[derive(Debug)]
+#[derive(EnumString, Display)]
+#[derive(Clone, Copy, PartialEq, Default)]
+#[strum(serialize_all = "kebab_case")]
+pub enum InputFormat {
+	#[default]
+	Nfl,
+	Dxf,
+	/// The model, as written by --to json
+	Json,
+	/// The model, as written by --to yaml
+	Yaml,
+}
+
+impl InputFormat {
+	/// The extensions files of this format can have. DXF files are
+	/// recognised when reading NFL, so they count as NFL here too.
+	pub fn extensions(&self) -> &'static [&'static str] {
+		match self {
+			InputFormat::Nfl => &[NFL_EXTENSION, DXF_EXTENSION],
+			InputFormat::Dxf => &[DXF_EXTENSION],
+			InputFormat::Json => &[JSON_EXTENSION],
+			InputFormat::Yaml => &[YAML_EXTENSION, YML_EXTENSION],
+		}
+	}
 }
 
 
 #[derive(Debug, StructOpt)]
-#[structopt(name = "example", about = "To be written.")]
+#[structopt(
+    name = "nfl2mac",
+    about = "Prepares NFL cross-sections for meshing in ANSYS.",
+    long_about = "Prepares NFL cross-sections for meshing in ANSYS.\n\n\
+        Parts in an NFL export often touch without sharing endpoints, which \
+        stops ANSYS from gluing them together. nfl2mac splits their lines \
+        wherever they overlap and writes the result as NFL or as an APDL macro.",
+    after_help = "EXIT CODES:\n    \
+        0    Success\n    \
+        1    Bad command line or project file, or any other error\n    \
+        2    Success, but with warnings\n    \
+        3    An input file could not be parsed\n    \
+        4    The geometry failed a check, or the files compared differ\n    \
+        5    A file could not be read or written"
+)]
 pub struct Config {
-    /// File to read
-    #[structopt(parse(from_os_str), name = "FILE")]
-	pub target: PathBuf,
-
     /// Makes output more verbose
     ///
     /// Adding extra v's (i.e. -vvv) increases verbosity, up to 4 times.
     /// Stages of verbosity are "error", "warn", "info", "debug", and "trace",
     /// with "error" being the defualt.
-    #[structopt(short = "v", long = "verbose", parse(from_occurrences))]
+    #[structopt(short = "v", long = "verbose", parse(from_occurrences), global = true)]
     pub verbose: usize,
 
-    /// Suppresses output, overriding -v
-    #[structopt(short, long)]
+    /// Only reports errors, overriding -v
+    ///
+    /// Summaries, warnings and logging are left out. The exit code still says whether there were warnings.
+    #[structopt(short, long, global = true, conflicts_with = "no-quiet")]
     pub quiet: bool,
 
+    /// Reports as usual, even if the project file says to be quiet
+    #[structopt(long, global = true)]
+    pub no_quiet: bool,
+
+    /// Format of errors and warnings: "text" or "json"
+    ///
+    /// JSON diagnostics are written to stderr, one object per line, and
+    /// end with a summary holding the exit code.
+    #[structopt(long, global = true, default_value = "text")]
+    pub format: diag::Format,
+
+    #[structopt(subcommand)]
+    pub command: Command,
+
+    /// The project file the settings were taken from, if any
+    #[structopt(skip)]
+    pub project: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Splits lines so that touching parts share their endpoints
+    ///
+    /// The result is written next to <FILE>, with "-split" added to its name.
+    Split {
+        #[structopt(flatten)]
+        input: InputOpts,
+
+        #[structopt(flatten)]
+        output: OutputOpts,
+
+        /// Keeps running, and splits files again whenever they change
+        ///
+        /// Once it has written a file, it overwrites it each time as if --force
+        /// was given. Files that were there before still need --force.
+        #[structopt(short, long)]
+        watch: bool,
+
+        /// Shows what splitting would do, without writing anything
+        #[structopt(short = "n", long, conflicts_with_all = &["watch", "report"])]
+        dry_run: bool,
+    },
+
+    /// Converts a file to another format, such as an APDL macro
+    ///
+    /// The result is written next to <FILE>, with the new format's extension.
+    Convert {
+        #[structopt(flatten)]
+        input: InputOpts,
+
+        #[structopt(flatten)]
+        output: OutputOpts,
+
+        /// Format to convert to: "mac", "nfl", "dxf", "svg", "ggb", "json" or "yaml"
+        #[structopt(long, default_value = "mac")]
+        to: OutputFormat,
+
+        /// Format to read: "nfl", "dxf", "json" or "yaml"
+        ///
+        /// DXF files are recognised without this.
+        #[structopt(long, default_value = "nfl")]
+        from: InputFormat,
+
+        /// Splits the geometry before converting it
+        #[structopt(long)]
+        split: bool,
+
+        /// Keeps running, and converts files again whenever they change
+        ///
+        /// Once it has written a file, it overwrites it each time as if --force
+        /// was given. Files that were there before still need --force.
+        #[structopt(short, long)]
+        watch: bool,
+    },
+
+    /// Prints a summary of what's in a file
+    Info {
+        #[structopt(flatten)]
+        input: InputOpts,
+    },
+
+    /// Checks that a file can be read and is already split
+    ///
+    /// Nothing is written. Fails if any parts still overlap without
+    /// sharing endpoints.
+    Check {
+        #[structopt(flatten)]
+        input: InputOpts,
+    },
+
+    /// Compares the geometry in two files
+    ///
+    /// Entities are matched by level and position, not by label, so
+    /// renumbered files compare equal.
+    Diff {
+        /// File to compare from
+        #[structopt(parse(from_os_str))]
+        old: PathBuf,
+
+        /// File to compare to
+        #[structopt(parse(from_os_str))]
+        new: PathBuf,
+
+        /// Encoding of both files; see `split --help`
+        #[structopt(long)]
+        encoding: Option<TextEncoding>,
+
+        /// How far apart points can be and still count as the same
+        #[structopt(short, long, default_value = "0.000001")]
+        line_precision: f64,
+    },
+}
+
+/// Options for reading the input files, shared by most commands.
+#[derive(Debug, StructOpt)]
+pub struct InputOpts {
+    /// Files to read
+    ///
+    /// Directories are searched recursively for files matching --glob.
+    #[structopt(parse(from_os_str), name = "FILE", required = true)]
+	pub targets: Vec<PathBuf>,
+
+    /// Pattern that files found in directories must match
+    ///
+    /// Files given directly are always read. Matching ignores case. If not
+    /// given, *.nfl and *.dxf files are found; convert with --from finds
+    /// files with that format's extensions instead, such as *.json.
+    #[structopt(long)]
+    pub glob: Option<String>,
+
+    /// Number of files to process at once
+    ///
+    /// If not given, this is the number of CPU cores.
+    #[structopt(short, long)]
+    pub jobs: Option<usize>,
+
     /// Type of the file being read
     ///
-    /// If not given, will attempt to deduce it from <target>
+    /// If not given, will attempt to deduce it from the contents of <target>.
+    /// NFL files with no overlaps left to split are taken to be SplitNFL.
     #[structopt(long)]
 	pub target_type: Option<FileType>,
 
+    /// Encoding of <target> and the output: "utf-8", "windows-1252" or "latin-1"
+    ///
+    /// If not given, <target> is read as UTF-8 if it's valid UTF-8, and as
+    /// Windows-1252 otherwise. The output uses the same encoding.
+    #[structopt(long)]
+    pub encoding: Option<TextEncoding>,
+
     /// Precision used when checking for overlaps in lines
     ///
     /// In the future, this may be auto-detected based on the part.
     /// Making this negative will cause lines to not be broken up.
     #[structopt(short, long, default_value = "0.000001")]
-    pub line_precision: f64
+    pub line_precision: f64,
+
+    /// Only reads these levels, e.g. "1,2,10-20"
+    #[structopt(long)]
+    pub levels: Option<LevelSet>,
+
+    /// Leaves out these levels, e.g. "38,90-99"
+    #[structopt(long)]
+    pub exclude_levels: Option<LevelSet>,
+
+    /// Only reads parts with this attribute, e.g. "COLOR=3"
+    ///
+    /// Can be given more than once; parts matching any of them are read.
+    /// Just "COLOR" matches any part with a COLOR at all.
+    #[structopt(long, number_of_values = 1)]
+    pub attr: Vec<AttrMatch>,
+
+    /// Leaves out parts with this attribute, e.g. "COLOR=7"
+    #[structopt(long, number_of_values = 1)]
+    pub exclude_attr: Vec<AttrMatch>,
+
+    /// Moves parts to another level, e.g. "38=20" or "30-39=20"
+    ///
+    /// Parts that end up on the same level are merged. This happens after
+    /// the levels have been filtered, so filters use the original levels.
+    #[structopt(long, number_of_values = 1)]
+    pub remap: Vec<Remap>,
+
+    /// Puts a DXF layer on a level, e.g. "STATOR=20"
+    ///
+    /// Layers named with a number go on that level, and layers named in
+    /// the project file's [levels] go on theirs. Any others go on new levels
+    /// after the highest one.
+    #[structopt(long, number_of_values = 1)]
+    pub layer: Vec<LayerLevel>,
+
+    /// Line precisions for particular levels, from the project file
+    #[structopt(skip)]
+    pub level_precision: BTreeMap<u64, f64>,
+
+    /// What the files are read as, from convert's --from
+    #[structopt(skip)]
+    pub from: InputFormat,
+}
+
+/// Where output ends up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Destination {
+    Stdout,
+    File(PathBuf),
+}
+
+/// Options for writing, shared by the commands that write files.
+#[derive(Debug, Clone, StructOpt)]
+pub struct OutputOpts {
+    /// File to write the result to, or "-" for stdout
+    ///
+    /// If not given, the result goes next to <target>.
+    #[structopt(short, long, parse(from_os_str))]
+    pub output: Option<PathBuf>,
+
+    /// Directory to write the result to, keeping its usual name
+    ///
+    /// The directory is created if it doesn't exist yet. Inputs whose
+    /// outputs would get the same name, such as a/model.nfl and
+    /// b/model.nfl, are refused.
+    #[structopt(long, parse(from_os_str), conflicts_with = "output")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Overwrites the output if it already exists
+    #[structopt(short, long, conflicts_with = "no-force")]
+    pub force: bool,
+
+    /// Refuses to overwrite outputs, even if the project file says to
+    #[structopt(long)]
+    pub no_force: bool,
+
+    /// Maximum length of lines in the output, including the '$'
+    ///
+    /// Records that don't fit are continued on the next line.
+    #[structopt(short, long, default_value = "79")]
+    pub max_line_len: usize,
+
+    /// Number of significant digits to write numbers with
+    ///
+    /// If not given, numbers are written with as many digits as it takes
+    /// to read them back exactly.
+    #[structopt(long)]
+    pub sig_digits: Option<usize>,
+
+    /// Writes whole numbers with a trailing dot (i.e. "120.")
+    ///
+    /// This matches what the original exporter does.
+    #[structopt(long, conflicts_with = "no-trailing-dot")]
+    pub trailing_dot: bool,
+
+    /// Writes whole numbers without a trailing dot, even if the project
+    /// file asks for one
+    #[structopt(long)]
+    pub no_trailing_dot: bool,
+
+    /// Numbers with a decimal exponent below this use scientific notation
+    #[structopt(long, allow_hyphen_values = true)]
+    pub sci_min_exp: Option<i32>,
+
+    /// Numbers with a decimal exponent above this use scientific notation
+    #[structopt(long, allow_hyphen_values = true)]
+    pub sci_max_exp: Option<i32>,
+
+    /// How entities are numbered: "global", "per-type" or "per-level"
+    ///
+    /// If not given, the scheme used by <target> is kept.
+    #[structopt(long)]
+    pub label_scheme: Option<LabelScheme>,
+
+    /// Number of digits in entity labels
+    ///
+    /// If not given, the width most labels in <target> have is kept, which
+    /// is usually 5. Labels that ran wider than that are renumbered.
+    #[structopt(long)]
+    pub label_width: Option<usize>,
+
+    /// Line endings to write: "lf" or "crlf"
+    ///
+    /// If not given, the line endings of <target> are kept.
+    #[structopt(long)]
+    pub line_ending: Option<LineEnding>,
+
+    /// Ends the output with a SUB character, as the CAD importer expects
+    ///
+    /// If neither this nor --no-eof-marker is given, the output has one
+    /// if <target> did.
+    #[structopt(long, conflicts_with = "no-eof-marker")]
+    pub eof_marker: bool,
+
+    /// Leaves the SUB character off the end of the output
+    #[structopt(long)]
+    pub no_eof_marker: bool,
+
+    /// Added to the name of split files
+    #[structopt(long, default_value = "-split")]
+    pub split_suffix: String,
+
+    /// Added to the name of converted files that keep their extension
+    #[structopt(long, default_value = "-converted")]
+    pub convert_suffix: String,
+
+    /// Writes a JSON report of everything that was split to this file
+    ///
+    /// Like the other outputs, an existing report is only replaced with --force.
+    #[structopt(long, parse(from_os_str), conflicts_with = "watch")]
+    pub report: Option<PathBuf>,
+
+    /// Marks where lines were split in DXF output
+    ///
+    /// Only does anything when converting with --split. SVG and GeoGebra
+    /// output always mark them.
+    #[structopt(long)]
+    pub mark_splits: bool,
+
+    /// Names for particular levels, from the project file
+    #[structopt(skip)]
+    pub level_names: BTreeMap<u64, String>,
 }
 
 impl Config {
-	pub fn new() -> Result<Config, &'static str> {
+	/// Reads the command line alone. The project file is left to
+	/// `load_project`, so that errors in it can be reported the way the
+	/// command line asked for.
+	pub fn from_args() -> (Config, ArgMatches<'static>) {
+        let matches = Config::clap().get_matches();
+        let mut conf = Config::from_clap(&matches);
 
-        let mut conf = Config::from_args();
+        if let Command::Convert { input, from, .. } = &mut conf.command {
+            input.from = *from;
+        }
+
+        (conf, matches)
+	}
 
-        // If we're missing the target type, we'll attempt to deduce it
-        if conf.target_type.is_none() {
+	/// Fills in anything the command line didn't give from the project
+	/// file, if there is one.
+	pub fn load_project(&mut self, matches: &ArgMatches) -> Result<(), String> {
+        if let Some(path) = project::find(self.command.first_target()) {
+            let project = Project::load(&path)?;
+            project.apply(self, matches)?;
+            self.project = Some(path);
+        }
 
-            if conf.target_name().ends_with(
-                    format!("{}{}", SPLIT_SUFFIX, NFL_EXTENSION).as_str()
-            ) {
-                conf.target_type = Some(FileType::SplitNFL);
+        Ok(())
+	}
+}
+
+impl Command {
+    /// The file or directory we're mainly working on, which is where the
+    /// project file gets looked for.
+    pub fn first_target(&self) -> &Path {
+        match self {
+            Command::Split { input, .. }
+            | Command::Convert { input, .. }
+            | Command::Info { input }
+            | Command::Check { input } => &input.targets[0],
+            Command::Diff { old, .. } => old,
+        }
+    }
+}
+
+impl InputOpts {
+    /// Gets every file to be read. Directories are searched recursively,
+    /// and the files found in them are sorted so runs are repeatable.
+    pub fn files(&self) -> Result<Vec<PathBuf>, Failure> {
+        let patterns = self.patterns()?;
+        let mut result = Vec::new();
+
+        for target in &self.targets {
+            if target.is_dir() {
+                find_files(target, &patterns, &mut result)
+                    .map_err(|e| Failure::new(Status::Io, format!("Could not search {:?}: {}", target, e)))?;
             }
             else {
-                conf.target_type = Some(FileType::RawNFL);
+                result.push(target.clone());
             }
+        }
+
+        Ok(result)
+    }
 
+    /// Gets the type of the target, working it out from its contents if
+    /// it wasn't given.
+    pub fn target_type(&self, target: &Path) -> Result<FileType, Failure> {
+        match &self.target_type {
+            Some(t) => Ok(t.clone()),
+            None => FileType::detect(target, |t| self.read(t), |a, b| self.precision(a, b)),
         }
+    }
 
-        Ok(conf)
+    /// Gets the line precision to use between two levels. Levels can have
+    /// their own in the project file; if both do, the looser one wins.
+    pub fn precision(&self, a: u64, b: u64) -> f64 {
+        let a = self.level_precision.get(&a);
+        let b = self.level_precision.get(&b);
 
-	}
+        match (a, b) {
+            (None, None) => self.line_precision,
+            _ => a.into_iter().chain(b).cloned().fold(f64::MIN, f64::max),
+        }
+    }
 
-    pub fn target_name(&self) -> &str {
-        self.target
-            .file_name().expect("Bad path")
-            .to_str().expect("Bad path")
+    /// Reads the target, then drops and moves levels as asked.
+    pub fn read(&self, target: &Path) -> Result<Assembly, Failure> {
+        self.read_as(target, InputFormat::Nfl)
     }
-	
-	pub fn split_dest(&self) -> PathBuf {
-		let mut new_name = String::from(self.target_name());
-		new_name.insert_str(new_name.len() - NFL_EXTENSION.len(), SPLIT_SUFFIX);
-		
-		self.target.with_file_name(new_name)
+
+    /// Reads the target in the given format, then drops and moves levels
+    /// as asked.
+    pub fn read_as(&self, target: &Path, format: InputFormat) -> Result<Assembly, Failure> {
+        let mut asm = match format {
+            InputFormat::Nfl => Assembly::read(target, self.encoding)?,
+            InputFormat::Dxf => Assembly::read_dxf(target, self.encoding, &self.layer_table())?,
+            InputFormat::Json => Assembly::from_json(&fs::read_to_string(target)?)?,
+            InputFormat::Yaml => Assembly::from_yaml(&fs::read_to_string(target)?)?,
+        };
+
+        let filter = self.level_filter();
+        if !filter.is_empty() || !self.remap.is_empty() {
+            let dropped = asm.filter_levels(&filter);
+            let moved = asm.remap_levels(&self.remap);
+            debug!("{:?}: dropped {} part(s), moved {} part(s)", target, dropped, moved);
+        }
+
+        Ok(asm)
+    }
+
+    /// Which level each DXF layer named with --layer goes on.
+    pub fn layer_table(&self) -> BTreeMap<String, u64> {
+        self.layer.iter().map(|l| (l.layer.clone(), l.level)).collect()
+    }
+
+    pub fn level_filter(&self) -> LevelFilter {
+        LevelFilter {
+            include: self.levels.clone(),
+            exclude: self.exclude_levels.clone(),
+            include_attrs: self.attr.clone(),
+            exclude_attrs: self.exclude_attr.clone(),
+        }
+    }
+
+    /// Checks whether the file is one that would be read, either because
+    /// it was given directly or because it's in one of the directories.
+    /// The path should be canonical.
+    pub fn includes(&self, path: &Path) -> bool {
+        let patterns = match self.patterns() {
+            Ok(p) => p,
+            Err(_) => return false,
+        };
+
+        self.targets.iter().filter_map(|t| fs::canonicalize(t).ok()).any(|t| {
+            if t.is_dir() {
+                path.starts_with(t) && matches_glob(path, &patterns)
+            }
+            else {
+                path == t
+            }
+        })
+    }
+
+    /// The patterns files in directories must match: --glob if it was
+    /// given, otherwise the extensions of the format being read.
+    fn patterns(&self) -> Result<Vec<Pattern>, Failure> {
+        let globs = match &self.glob {
+            Some(glob) => vec![glob.clone()],
+            None => self.from.extensions().iter().map(|e| format!("*.{}", e)).collect(),
+        };
+
+        globs.iter()
+            .map(|g| Pattern::new(g)
+                .map_err(|e| Failure::new(Status::Error, format!("Bad --glob {:?}: {}", g, e))))
+            .collect()
+    }
+}
+
+fn find_files(dir: &Path, patterns: &[Pattern], result: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            find_files(&path, patterns, result)?;
+        }
+        else if matches_glob(&path, patterns) {
+            result.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn matches_glob(path: &Path, patterns: &[Pattern]) -> bool {
+    let options = MatchOptions { case_sensitive: false, ..MatchOptions::new() };
+
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| patterns.iter().any(|p| p.matches_with(n, options)))
+}
+
+fn target_stem(target: &Path) -> Result<&str, Failure> {
+	target
+		.file_stem()
+		.and_then(|s| s.to_str())
+		.ok_or_else(|| no_file_name(target))
+}
+
+fn no_file_name(path: &Path) -> Failure {
+	Failure::new(Status::Error, format!("Can't work out a file name to write from {:?}", path))
+}
+
+impl OutputOpts {
+	/// Where the split file goes: next to the target, with the split
+	/// suffix added to its name. NFL extensions are kept as they are
+	/// (including their case); anything else ends up as ".nfl".
+	pub fn split_dest(&self, target: &Path) -> Result<PathBuf, Failure> {
+		let extension = match target.extension().and_then(|e| e.to_str()) {
+			Some(e) if e.eq_ignore_ascii_case(NFL_EXTENSION) => e,
+			_ => NFL_EXTENSION,
+		};
+
+		Ok(target.with_file_name(
+			format!("{}{}.{}", target_stem(target)?, self.split_suffix, extension)
+		))
 	}
+
+	/// Where converted files go: next to the target, with the extension
+	/// of the new format. If that would overwrite the target, the convert
+	/// suffix is added to the name.
+	pub fn convert_dest(&self, target: &Path, format: OutputFormat) -> Result<PathBuf, Failure> {
+		let same_format = target.extension()
+			.and_then(|e| e.to_str())
+			.is_some_and(|e| e.eq_ignore_ascii_case(format.extension()));
+
+		let suffix = if same_format { self.convert_suffix.as_str() } else { "" };
+
+		Ok(target.with_file_name(
+			format!("{}{}.{}", target_stem(target)?, suffix, format.extension())
+		))
+	}
+
+    /// Works out where the output goes, given where it would go by default.
+    pub fn dest(&self, default: PathBuf) -> Result<Destination, Failure> {
+        Ok(match (&self.output, &self.output_dir) {
+            (Some(o), _) if o.as_os_str() == STDOUT_NAME => Destination::Stdout,
+            (Some(o), _) => Destination::File(o.clone()),
+            (None, Some(dir)) => Destination::File(
+                dir.join(default.file_name().ok_or_else(|| no_file_name(&default))?)
+            ),
+            (None, None) => Destination::File(default),
+        })
+    }
+
+    /// Gets the options used for writing. Anything that wasn't given is
+    /// taken from the assembly that was read in, where possible.
+    pub fn write_options(&self, input: &InputOpts, asm: &Assembly) -> WriteOptions {
+        let default = NumberFormat::default();
+        let label_width = self.label_width.unwrap_or_else(|| asm.detect_label_width());
+
+        let overflowing = asm.overflowing_labels(label_width);
+        if let Some(first) = overflowing.first() {
+            warn!("{} labels in the input are wider than {} digits, such as {}; they're renumbered in the output",
+                  overflowing.len(), label_width, first);
+        }
+
+        WriteOptions {
+            max_line_len: self.max_line_len,
+            number: NumberFormat {
+                sig_digits: self.sig_digits,
+                trailing_dot: self.trailing_dot,
+                sci_min_exp: self.sci_min_exp.unwrap_or(default.sci_min_exp),
+                sci_max_exp: self.sci_max_exp.unwrap_or(default.sci_max_exp),
+            },
+            label_scheme: self.label_scheme
+                .unwrap_or_else(|| asm.detect_label_scheme()),
+            label_width,
+            text: TextFormat {
+                encoding: input.encoding.unwrap_or(asm.text_format.encoding),
+                line_ending: self.line_ending.unwrap_or(asm.text_format.line_ending),
+                eof_marker: (asm.text_format.eof_marker || self.eof_marker)
+                    && !self.no_eof_marker,
+            },
+            level_names: self.level_names.clone(),
+            point_tolerance: input.line_precision,
+        }
+    }
 }
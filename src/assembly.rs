@@ -15,36 +15,190 @@
 
 //mod line;
 pub mod part;
+pub mod nfl;
+pub mod encoding;
+pub mod mac;
+pub mod diff;
+pub mod levels;
+pub mod report;
+pub mod validate;
+pub mod dxf;
+pub mod svg;
+pub mod ggb;
+pub mod color;
+mod draw;
+mod points;
 
-use std::fs::File;
-use std::io::{self, BufRead};
-use std::path::{Path, PathBuf};
-use std::collections::BTreeMap;
+use std::{fs, io, fmt, error};
+use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
 use part::Part;
+use nfl::{WriteOptions, LabelScheme, Labeler};
+use levels::{LevelFilter, Remap};
+use report::SplitReport;
+use encoding::{TextFormat, TextEncoding};
+use serde::{Deserialize, Serialize};
+use euclid::Box2D;
 
-const SUB_CHAR: u8 = 26;	// This is what is read if EOF is not understood
-
-#[derive(Debug)]
+/// A whole cross-section. This is also the top of the JSON and YAML model;
+/// see the README for its layout.
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Assembly {
+	/// Records before the first LEVEL/, as they were in the file
+	#[serde(default)]
 	pub header: Vec<String>,
-	pub footer: Vec<String>,
-	
+
+	/// One part per level, in level order when read from a file
+	#[serde(default)]
 	pub parts: Vec<Part>,
+
+	/// FINI/ and anything after it, as they were in the file
+	#[serde(default)]
+	pub footer: Vec<String>,
+
+	/// Encoding, line endings, etc. of the file this was read from
+	#[serde(skip)]
+	pub text_format: TextFormat,
+}
+
+/// How far apart lines can be and still count as overlapping. This is
+/// either one number for everything, or worked out from the levels of the
+/// two parts being compared.
+pub trait Margin {
+	fn between(&self, a: u64, b: u64) -> f64;
+}
+
+impl Margin for f64 {
+	fn between(&self, _: u64, _: u64) -> f64 {
+		*self
+	}
+}
+
+impl<F: Fn(u64, u64) -> f64> Margin for F {
+	fn between(&self, a: u64, b: u64) -> f64 {
+		self(a, b)
+	}
+}
+
+/// Why an assembly couldn't be read.
+#[derive(Debug)]
+pub enum ReadError {
+	/// The file couldn't be read at all
+	Io(io::Error),
+
+	/// The file was read, but isn't valid NFL
+	Parse(String),
 }
 
+impl fmt::Display for ReadError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ReadError::Io(e) => write!(f, "Could not read the input file: {}", e),
+			ReadError::Parse(e) => write!(f, "{}", e),
+		}
+	}
+}
+
+impl error::Error for ReadError {}
+
 // Used when reading
 enum FileSection { Header, Body(u64) , Footer }
 
+// Gets the number of digits in a label such as "L00017", or None if it
+// isn't a letter and a number
+fn label_width(label: &str) -> Option<usize> {
+	nfl::parse_label(label).map(|_| label.chars().count() - 1)
+}
+
 impl Assembly {
 
+	/// Creates an assembly with no parts, to be filled in with `add_part`.
+	/// It gets the usual FINI/ and STOP/ footer.
+	pub fn empty() -> Assembly {
+		Assembly {
+			header: Vec::new(),
+			footer: vec![String::from("FINI/"), String::from("STOP/")],
+			parts: Vec::new(),
+			text_format: TextFormat::default()
+		}
+	}
+
+	/// Gets the part on the given level, adding an empty one if there is
+	/// none yet. The attributes (e.g. ("COLOR", "GREEN")) are set on it
+	/// either way. New parts go before the first one with a higher level,
+	/// so parts that were in level order stay that way.
+	pub fn add_part<I, K, V>(&mut self, level: u64, attrs: I) -> &mut Part
+	where I: IntoIterator<Item = (K, V)>, K: Into<String>, V: Into<String>
+	{
+		// parts can be changed by anyone, so don't count on it being sorted
+		let i = match self.parts.iter().position(|p| p.level == level) {
+			Some(i) => i,
+			None => {
+				let i = self.parts.iter().position(|p| p.level > level).unwrap_or(self.parts.len());
+				self.parts.insert(i, Part::empty(level));
+				i
+			}
+		};
+
+		let part = &mut self.parts[i];
+		for (key, value) in attrs {
+			part.data.insert(key.into(), value.into());
+		}
+
+		part
+	}
+
+	/// Gets the smallest box that holds every curve, or None if there are
+	/// no curves.
+	pub fn bounding_box(&self) -> Option<Box2D<f64, f64>> {
+		self.parts.iter()
+			.flat_map(|p| &p.curves)
+			.map(|c| c.bounding_box())
+			.fold(None, |acc, b| Some(acc.map_or(b, |acc: Box2D<f64, f64>| acc.union(&b))))
+	}
+
 	/// Creates a new assembly based on the given input file.
 	/// 
 	/// # Arguments
 	/// 
 	/// * infile: path to file
-	pub fn new(infile: &PathBuf) -> Result<Assembly, &'static str> {
+	pub fn new(infile: impl AsRef<Path>) -> Result<Assembly, ReadError> {
+		Assembly::read(infile, None)
+	}
+
+	/// Like `new`, but lets the encoding be given instead of detected.
+	pub fn read(infile: impl AsRef<Path>, encoding: Option<TextEncoding>)
+		-> Result<Assembly, ReadError>
+	{
+		let bytes = fs::read(infile).map_err(ReadError::Io)?;
+		let (text, text_format) = TextFormat::decode(&bytes, encoding);
+
+		let mut result = Assembly::from_nfl(&text)?;
+		result.text_format = text_format;
+
+		Ok(result)
+	}
+
+	/// Reads a DXF file. Layers are put on levels as `dxf::read` explains.
+	pub fn read_dxf(infile: impl AsRef<Path>, encoding: Option<TextEncoding>, layers: &BTreeMap<String, u64>)
+		-> Result<Assembly, ReadError>
+	{
+		let bytes = fs::read(infile).map_err(ReadError::Io)?;
+		let (text, text_format) = TextFormat::decode(&bytes, encoding);
+
+		let mut result = Assembly::from_dxf(&text, layers)?;
+		result.text_format = text_format;
+
+		Ok(result)
+	}
+
+	/// Creates a new assembly from the text of an ASCII DXF file.
+	pub fn from_dxf(text: &str, layers: &BTreeMap<String, u64>) -> Result<Assembly, ReadError> {
+		dxf::read(text, layers)
+	}
 
-		// TODO test file missing
+	/// Creates a new assembly from the text of an NFL file.
+	pub fn from_nfl(text: &str) -> Result<Assembly, ReadError> {
 		
 		let part_begin = "LEVEL/";
 		let footer_begin = "FINI/";
@@ -52,91 +206,251 @@ impl Assembly {
 		let mut result = Assembly {
 			header: Vec::new(),
 			footer: Vec::new(),
-			parts: Vec::new()
+			parts: Vec::new(),
+			text_format: TextFormat::default()
 		};
 		
-		if let Ok(lines) = read_lines(infile) {
-			// Consumes the iterator, returns an (Optional) String
-			let mut current_section = FileSection::Header;
-
-			// Was originally using HashMap, but it would mix up the order
-			// of the parts, which meant our output changed with every run.
-			// ...not great for diffs and stuff.
-			let mut part_data: BTreeMap<u64, Vec<String>> = BTreeMap::new();
-
-			for line in lines {
-				
-				if let Ok(ip) = line {
-					// This is so that Eclipse understands what this is
-					let ip: String = ip;
-
-					// This always denotes a new part, whether in the header
-					// or in the body.
-					if ip.starts_with(part_begin) {
-						current_section = FileSection::Body(
-							ip.strip_prefix(part_begin)
-								.unwrap()
-								.parse()
-								.unwrap_or(0)
-						);
-					}
-					else if !ip.is_empty() && ip.as_bytes()[0] != SUB_CHAR {
-						// On the line above, we check SUB_CHAR because Rust
-						// sometimes reads that at the end of the file.
-
-						match current_section {
-							FileSection::Header => result.header.push(ip),
-							FileSection::Footer => result.footer.push(ip),
-							FileSection::Body(level) => {
-								if ip.eq(footer_begin) {
-									current_section = FileSection::Footer;
-									result.footer.push(ip);
-								}
-								else {
-									part_data.entry(level)
-										.or_insert(Vec::new()).push(ip);
-								}
-							}
+		let mut current_section = FileSection::Header;
+
+		// Was originally using HashMap, but it would mix up the order
+		// of the parts, which meant our output changed with every run.
+		// ...not great for diffs and stuff.
+		let mut part_data: BTreeMap<u64, Vec<String>> = BTreeMap::new();
+
+		for ip in text.lines() {
+			let ip = String::from(ip);
+			let (content, _) = nfl::strip_continuation(&ip);
+
+			// This always denotes a new part, whether in the header
+			// or in the body.
+			if let Some(level) = content.strip_prefix(part_begin) {
+				current_section = FileSection::Body(
+					level.trim().parse().map_err(|_| {
+						ReadError::Parse(format!("Bad level in {:?}", content))
+					})?
+				);
+			}
+			else if !ip.is_empty() {
+				match current_section {
+					FileSection::Header => result.header.push(ip),
+					FileSection::Footer => result.footer.push(ip),
+					FileSection::Body(level) => {
+						if content == footer_begin {
+							current_section = FileSection::Footer;
+							result.footer.push(ip);
+						}
+						else {
+							part_data.entry(level)
+								.or_default().push(ip);
 						}
 					}
 				}
 			}
-			
-			for entry in part_data {
-				result.parts.push(Part::new(entry.0, entry.1));
-			}
+		}
+		
+		for (level, data) in part_data {
+			let part = Part::new(level, data)
+				.map_err(|e| ReadError::Parse(format!("LEVEL/{}: {}", level, e)))?;
+			result.parts.push(part);
 		}
 		
 		Ok(result)
 	}
 	
-    pub fn split(&mut self, line_overlap_margin: f64) {
+	/// Reads an assembly from JSON laid out like `to_json` writes it.
+	/// Parts are put in level order, and parts on the same level merged.
+	/// A model with no footer gets the usual FINI/ and STOP/.
+	pub fn from_json(text: &str) -> Result<Assembly, ReadError> {
+		let result: Assembly = serde_json::from_str(text)
+			.map_err(|e| ReadError::Parse(format!("Bad JSON: {}", e)))?;
+
+		Ok(result.tidied())
+	}
+
+	/// Reads an assembly from YAML laid out like `to_yaml` writes it, with
+	/// the same tidying up as `from_json`.
+	pub fn from_yaml(text: &str) -> Result<Assembly, ReadError> {
+		let result: Assembly = serde_yaml::from_str(text)
+			.map_err(|e| ReadError::Parse(format!("Bad YAML: {}", e)))?;
+
+		Ok(result.tidied())
+	}
+
+	// Models written by hand can have their parts in any order and leave
+	// out the footer
+	fn tidied(mut self) -> Assembly {
+		self.remap_levels(&[]);
+		if self.footer.is_empty() {
+			self.footer = Assembly::empty().footer;
+		}
+
+		self
+	}
+
+	/// Drops every part the filter doesn't keep. Returns how many parts
+	/// were dropped.
+	pub fn filter_levels(&mut self, filter: &LevelFilter) -> usize {
+		let before = self.parts.len();
+		self.parts.retain(|p| filter.keeps(p));
+
+		before - self.parts.len()
+	}
+
+	/// Moves parts to other levels. Parts that end up on the same level
+	/// are merged into one. Returns how many parts were moved.
+	pub fn remap_levels(&mut self, remaps: &[Remap]) -> usize {
+		let mut moved = 0;
+		let mut parts: BTreeMap<u64, Part> = BTreeMap::new();
+
+		for mut p in self.parts.drain(..) {
+			let level = levels::remapped(p.level, remaps);
+			if level != p.level {
+				moved += 1;
+				p.level = level;
+			}
+
+			match parts.get_mut(&level) {
+				Some(existing) => existing.merge(p),
+				None => { parts.insert(level, p); },
+			}
+		}
+
+		self.parts = parts.into_values().collect();
+		moved
+	}
+
+    /// Splits lines wherever parts overlap, so that they share endpoints.
+    /// Returns what was split, and where.
+    pub fn split<M: Margin>(&mut self, line_overlap_margin: M) -> SplitReport {
+        let mut report = SplitReport::default();
+
         for i in 0..self.parts.len() {
             for j in (i+1)..self.parts.len() {
                 // This is a weird workaround... basically, forcing Rust
                 // to allow us to pass in both indicies mutably.
                 let (a, b) = self.parts.split_at_mut(j);
 
-                Part::resolve_overlaps(&mut a[i], &mut b[0], line_overlap_margin);
+                let m = line_overlap_margin.between(a[i].level, b[0].level);
+                report.add(Part::resolve_overlaps(&mut a[i], &mut b[0], m));
             }
         }
         
+        report
     }
 
+	/// Checks whether the assembly is already split; that is, whether there
+	/// are no overlaps between parts left that `split` would break up.
+	pub fn is_conforming<M: Margin>(&self, line_overlap_margin: M) -> bool {
+		self.unresolved_overlaps(line_overlap_margin).is_empty()
+	}
+
+	/// Gets the levels of every pair of parts that overlap somewhere without
+	/// sharing endpoints.
+	pub fn unresolved_overlaps<M: Margin>(&self, line_overlap_margin: M) -> Vec<(u64, u64)> {
+		let mut result = Vec::new();
+
+		for i in 0..self.parts.len() {
+			for j in (i+1)..self.parts.len() {
+				let (a, b) = (&self.parts[i], &self.parts[j]);
+
+				if Part::needs_split(a, b, line_overlap_margin.between(a.level, b.level)) {
+					result.push((self.parts[i].level, self.parts[j].level));
+				}
+			}
+		}
+
+		result
+	}
+
+	/// Checks for anything that would stop the assembly from meshing,
+	/// such as parts that still need splitting.
+	pub fn validate<M: Margin>(&self, line_overlap_margin: M) -> Vec<validate::Problem> {
+		validate::validate(self, line_overlap_margin)
+	}
+
+	/// Options that write the assembly out the way it was read: the same
+	/// label scheme and width, encoding, line endings and EOF marker.
+	pub fn write_options(&self) -> WriteOptions {
+		WriteOptions {
+			label_scheme: self.detect_label_scheme(),
+			label_width: self.detect_label_width(),
+			text: self.text_format.clone(),
+			..WriteOptions::default()
+		}
+	}
+
+	/// Works out which label scheme the file this came from used.
+	pub fn detect_label_scheme(&self) -> LabelScheme {
+		LabelScheme::detect(self.parts.iter().flat_map(|p| {
+			p.labels().map(move |l| (p.level, l))
+		}))
+	}
+
+	/// Works out how wide the labels in the file this came from were: the
+	/// width most of them have, or the default if there are none. Labels
+	/// wider than that are ones that overflowed it; see
+	/// `overflowing_labels`.
+	pub fn detect_label_width(&self) -> usize {
+		let mut counts: HashMap<usize, usize> = HashMap::new();
+		for width in self.label_widths() {
+			*counts.entry(width).or_insert(0) += 1;
+		}
+
+		// Ties go to the narrower width, so the wider labels count as
+		// overflowed
+		counts.into_iter()
+			.max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+			.map_or(nfl::DEFAULT_LABEL_WIDTH, |(width, _)| width)
+	}
+
+	/// Gets the labels read in that have more than `width` digits, i.e.
+	/// "L100000" when the width is 5.
+	pub fn overflowing_labels(&self, width: usize) -> Vec<&str> {
+		self.parts.iter()
+			.flat_map(|p| p.labels())
+			.filter(|l| label_width(l).is_some_and(|w| w > width))
+			.collect()
+	}
+
+	// The number of digits in each label that has a number at all
+	fn label_widths(&self) -> impl Iterator<Item = usize> + '_ {
+		self.parts.iter().flat_map(|p| p.labels()).filter_map(label_width)
+	}
+
+	/// Checks that every entity label will fit in the label width when
+	/// written with the given options.
+	pub fn check_labels(&self, opts: &WriteOptions) -> Result<(), String> {
+		let mut labeler = Labeler::new(opts.label_scheme);
+		for p in &self.parts {
+			for c in &p.curves { labeler.next(c.prefix(), p.level); }
+		}
+
+		if labeler.max_issued() > opts.max_label() {
+			Err(format!(
+				"Labels go up to {}, which doesn't fit in {} digits. \
+				 Use a wider label width or a different label scheme.",
+				labeler.max_issued(), opts.label_width
+			))
+		}
+		else {
+			Ok(())
+		}
+	}
+
 	/// Converts the assembly to an NFL string for writing.
 	/// Contains newlines, does not end in newline.
-	pub fn to_nfl(&self) -> String {
+	pub fn to_nfl(&self, opts: &WriteOptions) -> String {
 		let mut result: String = String::new();
 		
 		for l in &self.header {
-			result += &l;
+			result += l;
 			result.push('\n');
 		}
 
 		// Individual parts start at ID 1
-		let mut id = 1;
+		let mut labeler = Labeler::new(opts.label_scheme);
 		for p in &self.parts {
-			result += &p.to_nfl(&mut id);
+			result += &p.to_nfl(&mut labeler, opts);
 		}
 
 
@@ -147,16 +461,168 @@ impl Assembly {
 
 		// Do this order because NFL files have this weird symbol at the
 		// very end, and we probably don't want to dump a newline afterward.
-		result.remove(result.len()-1);
+		if result.ends_with('\n') {
+			result.pop();
+		}
 		
 		result
 	}
+
+	/// Converts the assembly to an ANSYS APDL macro that recreates it.
+	pub fn to_mac(&self, opts: &WriteOptions) -> String {
+		mac::write(self, opts)
+	}
+
+	/// Converts the assembly to NFL, encoded and laid out as given in
+	/// `opts.text`, ready to be written to a file.
+	pub fn to_bytes(&self, opts: &WriteOptions) -> Vec<u8> {
+		opts.text.encode(&self.to_nfl(opts))
+	}
+
+	/// Converts the assembly to ASCII DXF, marking the given split points.
+	pub fn to_dxf(&self, opts: &WriteOptions, split_points: &[report::Point]) -> String {
+		dxf::write(self, opts, split_points)
+	}
+
+	/// Draws the assembly as SVG, marking the given split points and
+	/// wherever parts run along each other.
+	pub fn to_svg<M: Margin>(&self, opts: &WriteOptions, line_overlap_margin: M,
+	                         split_points: &[report::Point]) -> String
+	{
+		svg::write(self, opts, line_overlap_margin, split_points)
+	}
+
+	/// Converts the assembly to a GeoGebra .ggb file, showing the given
+	/// split points.
+	pub fn to_ggb(&self, opts: &WriteOptions, split_points: &[report::Point]) -> Vec<u8> {
+		ggb::write(self, opts, split_points)
+	}
+
+	pub fn to_json(&self) -> String {
+		serde_json::to_string_pretty(self).expect("Assemblies always serialize")
+	}
+
+	pub fn to_yaml(&self) -> String {
+		serde_yaml::to_string(self).expect("Assemblies always serialize")
+	}
 }
 
-// Yoinked from
-// https://doc.rust-lang.org/rust-by-example/std_misc/file/read_lines.html
-fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-where P: AsRef<Path>, {
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn label_width() {
+		assert_eq!(Assembly::empty().detect_label_width(), nfl::DEFAULT_LABEL_WIDTH);
+
+		// The exporter ran past L99999; that doesn't make the width 6
+		let asm = Assembly::from_nfl(
+			"LEVEL/1\nL99998=LINE/0,0,1,0\nL99999=LINE/1,0,2,0\nL100000=LINE/2,0,3,0\nFINI/\n"
+		).unwrap();
+		assert_eq!(asm.detect_label_width(), 5);
+		assert_eq!(asm.overflowing_labels(5), vec!["L100000"]);
+
+		let asm = Assembly::from_nfl("LEVEL/1\nL0001=LINE/0,0,1,0\nFINI/\n").unwrap();
+		assert_eq!(asm.detect_label_width(), 4);
+		assert!(asm.overflowing_labels(4).is_empty());
+
+		// Blank and broken labels don't count
+		let asm = Assembly::from_nfl("LEVEL/1\n=LINE/0,0,1,0\nL=LINE/1,0,2,0\nL001=LINE/2,0,3,0\nFINI/\n").unwrap();
+		assert_eq!(asm.parts[0].labels().collect::<Vec<_>>(), vec!["L", "L001"]);
+		assert_eq!(asm.detect_label_width(), 3);
+		assert!(asm.overflowing_labels(0).contains(&"L001"));
+		assert_eq!(asm.to_nfl(&asm.write_options()).matches("=LINE/").count(), 3);
+	}
+
+	const MODEL: &str = "PARTNO/'Z:\\models\\test_assy.nfl',1\nTXTJUS/LEFT\n\
+	                     LEVEL/1\nCOLOR/GREEN\nFONT/SOLID\n\
+	                     L00001=LINE/0,0,2,0\n\
+	                     C00002=CIRCLE/CENTER,1,0,RADIUS,1,GOANG,0,ENDANG,180\n\
+	                     LEVEL/38\nCOLOR/RED\nL00003=LINE/1,0,3,0\n\
+	                     C00004=CIRCLE/CENTER,5,5,RADIUS,0.5\n\
+	                     FINI/\nSTOP/\n";
+
+	fn assert_same(a: &Assembly, b: &Assembly) {
+		assert_eq!(a.header, b.header);
+		assert_eq!(a.footer, b.footer);
+		assert_eq!(a.parts.len(), b.parts.len());
+
+		for (pa, pb) in a.parts.iter().zip(&b.parts) {
+			assert_eq!(pa.level, pb.level);
+			assert_eq!(pa.data, pb.data);
+			assert_eq!(pa.labels().collect::<Vec<_>>(), pb.labels().collect::<Vec<_>>());
+		}
+
+		let opts = WriteOptions::default();
+		assert_eq!(a.to_nfl(&opts), b.to_nfl(&opts));
+	}
+
+	#[test]
+	fn json() {
+		let asm = Assembly::from_nfl(MODEL).unwrap();
+		let read = Assembly::from_json(&asm.to_json()).unwrap();
+
+		assert_eq!(read.header, vec!["PARTNO/'Z:\\models\\test_assy.nfl',1", "TXTJUS/LEFT"]);
+		assert_eq!(read.parts[1].data.get("COLOR").map(String::as_str), Some("RED"));
+		assert_eq!(read.parts[0].labels().collect::<Vec<_>>(), vec!["L00001", "C00002"]);
+		assert_same(&asm, &read);
+	}
+
+	#[test]
+	fn yaml() {
+		let asm = Assembly::from_nfl(MODEL).unwrap();
+		assert_same(&asm, &Assembly::from_yaml(&asm.to_yaml()).unwrap());
+	}
+
+	#[test]
+	fn duplicate_levels() {
+		let json = r#"{
+			"parts": [
+				{ "level": 38, "attrs": { "COLOR": "RED" },
+				  "curves": [{ "type": "line", "p1": [1, 0], "p2": [3, 0] }] },
+				{ "level": 1, "curves": [{ "type": "arc", "center": [0, 0], "radius": 1 }] },
+				{ "level": 38, "attrs": { "FONT": "SOLID" },
+				  "curves": [{ "type": "line", "p1": [3, 0], "p2": [3, 1] }] }
+			]
+		}"#;
+
+		let yaml = "parts:\n\
+		            - { level: 38, attrs: { COLOR: RED }, curves: [{ type: line, p1: [1, 0], p2: [3, 0] }] }\n\
+		            - { level: 1, curves: [{ type: arc, center: [0, 0], radius: 1 }] }\n\
+		            - { level: 38, attrs: { FONT: SOLID }, curves: [{ type: line, p1: [3, 0], p2: [3, 1] }] }\n";
+
+		for asm in [Assembly::from_json(json).unwrap(), Assembly::from_yaml(yaml).unwrap()] {
+			assert_eq!(asm.parts.iter().map(|p| p.level).collect::<Vec<_>>(), vec![1, 38]);
+			assert_eq!(asm.parts[1].curves.len(), 2);
+			assert_eq!(asm.parts[1].data.len(), 2);
+			assert!(asm.header.is_empty());
+			assert_eq!(asm.footer, vec!["FINI/", "STOP/"]);
+		}
+	}
+
+	#[test]
+	fn empty_model() {
+		let asm = Assembly::from_json("{}").unwrap();
+		assert_eq!(asm.to_nfl(&WriteOptions::default()), "FINI/\nSTOP/");
+		assert_eq!(Assembly::from_yaml("{}").unwrap().footer, asm.footer);
+
+		// Nothing at all writes nothing at all
+		assert_eq!(Assembly::from_nfl("").unwrap().to_nfl(&WriteOptions::default()), "");
+	}
+
+	#[test]
+	fn add_part() {
+		let mut asm = Assembly::empty();
+		asm.add_part(3, vec![("COLOR", "RED")]);
+		asm.add_part(1, Vec::<(&str, &str)>::new());
+		asm.add_part(2, Vec::<(&str, &str)>::new());
+		assert_eq!(asm.parts.iter().map(|p| p.level).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+		// Levels already there are found even once the order is upset
+		asm.parts.reverse();
+		asm.add_part(3, vec![("FONT", "SOLID")]);
+		asm.add_part(1, Vec::<(&str, &str)>::new());
+		assert_eq!(asm.parts.len(), 3);
+		assert_eq!(asm.parts[0].data.len(), 2);
+	}
 }
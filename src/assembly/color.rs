@@ -0,0 +1,105 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// The colors a part can be given with COLOR/.
+//
+// NFL names the seven basic CAD colors, and files that came through DXF
+// can have their AutoCAD Color Index instead, so COLOR/ is read as either.
+// How each color is drawn is up to the writer.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    Red,
+    Yellow,
+    Green,
+    Cyan,
+    Blue,
+    Magenta,
+    White,
+}
+
+/// Each color with its NFL name and AutoCAD Color Index.
+const COLORS: [(Color, &str, i32); 7] = [
+    (Color::Red, "RED", 1), (Color::Yellow, "YELLOW", 2), (Color::Green, "GREEN", 3),
+    (Color::Cyan, "CYAN", 4), (Color::Blue, "BLUE", 5), (Color::Magenta, "MAGENTA", 6),
+    (Color::White, "WHITE", 7),
+];
+
+impl Color {
+    /// Gets the color for an AutoCAD Color Index, if it's one of the basic
+    /// seven. Layers that are turned off have negative indices; they get
+    /// their color all the same.
+    pub fn from_aci(aci: i32) -> Option<Color> {
+        COLORS.iter().find(|c| c.2 == aci.abs()).map(|c| c.0)
+    }
+
+    /// The AutoCAD Color Index, as written to DXF.
+    pub fn aci(self) -> i32 {
+        COLORS.iter().find(|c| c.0 == self).expect("Every color is in the table").2
+    }
+
+    /// The NFL name, i.e. "GREEN".
+    pub fn name(self) -> &'static str {
+        COLORS.iter().find(|c| c.0 == self).expect("Every color is in the table").1
+    }
+
+    /// Reads a COLOR/ value, which can be a name or an AutoCAD Color
+    /// Index. Anything else, such as colors with no name, gives None.
+    pub fn parse(value: &str) -> Option<Color> {
+        value.parse().ok()
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let found = match s.parse::<i32>() {
+            Ok(aci) => Color::from_aci(aci),
+            Err(_) => COLORS.iter().find(|c| c.1.eq_ignore_ascii_case(s)).map(|c| c.0),
+        };
+
+        found.ok_or_else(|| format!("{:?} is not a color name or one of the AutoCAD Color Indices 1-7", s))
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Color::parse("GREEN"), Some(Color::Green));
+        assert_eq!(Color::parse(" green "), Some(Color::Green));
+        assert_eq!(Color::parse("1"), Some(Color::Red));
+        assert_eq!(Color::parse("-5"), Some(Color::Blue));
+        assert_eq!(Color::parse("42"), None);
+        assert_eq!(Color::parse("PUCE"), None);
+
+        assert_eq!(Color::Magenta.aci(), 6);
+        assert_eq!(Color::Magenta.to_string(), "MAGENTA");
+    }
+}
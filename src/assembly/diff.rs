@@ -0,0 +1,175 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// Compares the geometry of two assemblies.
+//
+// Labels are ignored; entities are matched up by their level and position,
+// since the whole point of splitting is that the numbering changes.
+
+use std::fmt;
+use super::Assembly;
+use super::part::Part;
+use super::part::line::Line;
+use super::part::arc::{Arc, normalize_angle};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side { Old, New }
+
+#[derive(Debug)]
+pub enum Difference {
+    /// A whole level is only in one of the assemblies
+    Level { side: Side, level: u64 },
+
+    /// A line or arc is only in one of the assemblies
+    Entity { side: Side, level: u64, entity: String },
+
+    /// A part attribute such as COLOR/ is different (or missing)
+    Attribute { level: u64, key: String, old: Option<String>, new: Option<String> },
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Side::Old => write!(f, "-"),
+            Side::New => write!(f, "+"),
+        }
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difference::Level { side, level } =>
+                write!(f, "{} LEVEL/{}", side, level),
+            Difference::Entity { side, level, entity } =>
+                write!(f, "{} LEVEL/{}: {}", side, level, entity),
+            Difference::Attribute { level, key, old, new } =>
+                write!(f, "~ LEVEL/{}: {}/{} -> {}/{}", level,
+                       key, old.as_deref().unwrap_or("(none)"),
+                       key, new.as_deref().unwrap_or("(none)")),
+        }
+    }
+}
+
+/// Finds everything that differs between the two assemblies. Points closer
+/// together than max_dist (and angles closer than max_dist degrees) are
+/// considered the same.
+pub fn diff(old: &Assembly, new: &Assembly, max_dist: f64) -> Vec<Difference> {
+    let mut result = Vec::new();
+
+    for p in &old.parts {
+        match new.parts.iter().find(|q| q.level == p.level) {
+            Some(q) => diff_parts(p, q, max_dist, &mut result),
+            None => result.push(Difference::Level { side: Side::Old, level: p.level }),
+        }
+    }
+
+    for q in &new.parts {
+        if !old.parts.iter().any(|p| p.level == q.level) {
+            result.push(Difference::Level { side: Side::New, level: q.level });
+        }
+    }
+
+    result
+}
+
+fn diff_parts(old: &Part, new: &Part, max_dist: f64, result: &mut Vec<Difference>) {
+    let level = old.level;
+
+    let mut keys: Vec<&String> = old.data.keys().chain(new.data.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    for key in keys {
+        let (o, n) = (old.data.get(key), new.data.get(key));
+        if o != n {
+            result.push(Difference::Attribute {
+                level, key: key.clone(), old: o.cloned(), new: n.cloned()
+            });
+        }
+    }
+
+    let entity = |side, e: &dyn fmt::Display| {
+        Difference::Entity { side, level, entity: e.to_string() }
+    };
+
+    let (only_old, only_new) = unmatched(old.lines(), new.lines(), |a, b| same_line(a, b, max_dist));
+    result.extend(only_old.into_iter().map(|l| entity(Side::Old, l)));
+    result.extend(only_new.into_iter().map(|l| entity(Side::New, l)));
+
+    let (only_old, only_new) = unmatched(old.arcs(), new.arcs(), |a, b| same_arc(a, b, max_dist));
+    result.extend(only_old.into_iter().map(|a| entity(Side::Old, a)));
+    result.extend(only_new.into_iter().map(|a| entity(Side::New, a)));
+}
+
+/// Pairs up items from the two lists, and returns the ones left over.
+fn unmatched<'a, T, I, F>(old: I, new: I, same: F) -> (Vec<&'a T>, Vec<&'a T>)
+where I: Iterator<Item = &'a T>, F: Fn(&T, &T) -> bool
+{
+    let new: Vec<&T> = new.collect();
+    let mut matched = vec![false; new.len()];
+    let mut only_old = Vec::new();
+
+    for a in old {
+        let found = (0..new.len()).find(|&j| !matched[j] && same(a, new[j]));
+
+        match found {
+            Some(j) => matched[j] = true,
+            None => only_old.push(a),
+        }
+    }
+
+    let only_new = new.into_iter().zip(matched).filter(|(_, m)| !m).map(|(b, _)| b).collect();
+
+    (only_old, only_new)
+}
+
+fn same_line(a: &Line, b: &Line, max_dist: f64) -> bool {
+    let close = |i: usize, j: usize| (a[i] - b[j]).length() <= max_dist;
+
+    (close(0, 0) && close(1, 1)) || (close(0, 1) && close(1, 0))
+}
+
+fn same_arc(a: &Arc, b: &Arc, max_dist: f64) -> bool {
+    let angle_diff = normalize_angle(a.go_angle - b.go_angle);
+
+    (a.center - b.center).length() <= max_dist
+        && (a.radius - b.radius).abs() <= max_dist
+        && (a.sweep() - b.sweep()).abs() <= max_dist
+        && angle_diff.min(360.0 - angle_diff) <= max_dist
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reversed_lines_match() {
+        let a = Line::new(0., 0., 1., 1.);
+        let b = Line::new(1., 1., 0., 0.);
+        let c = Line::new(1., 1., 0., 0.5);
+
+        assert!(same_line(&a, &b, 0.000001));
+        assert!(!same_line(&a, &c, 0.000001));
+    }
+
+    #[test]
+    fn leftovers() {
+        let (o, n) = unmatched([1, 2, 2, 3].iter(), [2, 3, 4].iter(), |a, b| a == b);
+
+        assert_eq!(o, vec![&1, &2]);
+        assert_eq!(n, vec![&4]);
+    }
+}
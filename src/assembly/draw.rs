@@ -0,0 +1,36 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// What the writers that draw pictures have in common: SVG and GeoGebra
+// both frame the model the same way and write it as XML.
+
+use euclid::{Box2D, Point2D};
+use super::Assembly;
+
+/// Gets the area to draw: the assembly's bounding box with a little room
+/// around it, or a unit square if there's nothing in it. Lines along an
+/// axis still get some height or width.
+pub(crate) fn view_bounds(asm: &Assembly) -> Box2D<f64, f64> {
+    let bounds = asm.bounding_box()
+        .unwrap_or_else(|| Box2D::new(Point2D::new(0., 0.), Point2D::new(1., 1.)));
+
+    let pad = (bounds.width().max(bounds.height()) * 0.05).max(1e-6);
+    bounds.inflate(pad, pad)
+}
+
+/// Escapes text for use in XML.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
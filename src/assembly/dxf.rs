@@ -0,0 +1,533 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// ASCII DXF. A DXF file is a list of (group code, value) pairs, one per
+// line each. Entities start with a 0 group giving their type, and go on
+// until the next 0 group. Layers become parts.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use euclid::{Point2D, Vector2D};
+use log::*;
+
+use super::{Assembly, ReadError};
+use super::nfl::{self, WriteOptions};
+use super::report::Point;
+use super::part::Part;
+use super::part::line::Line;
+use super::part::arc::{Arc, normalize_angle};
+use super::part::curve::Curve;
+use super::color::Color;
+
+/// The layer split points are written to.
+pub const SPLIT_LAYER: &str = "SPLIT_POINTS";
+
+// Magenta stands out against the usual part colors
+const SPLIT_COLOR: i32 = 6;
+
+// What layers without a COLOR/ get
+const DEFAULT_COLOR: i32 = 7;
+
+/// Puts a DXF layer on a level, i.e. "STATOR=20".
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerLevel {
+    pub layer: String,
+    pub level: u64,
+}
+
+impl FromStr for LayerLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LayerLevel, String> {
+        let bad = || format!("Bad layer mapping {:?}; expected LAYER=LEVEL", s);
+        let (layer, level) = s.rsplit_once('=').ok_or_else(bad)?;
+
+        Ok(LayerLevel {
+            layer: String::from(layer.trim()),
+            level: level.trim().parse().map_err(|_| bad())?,
+        })
+    }
+}
+
+impl fmt::Display for LayerLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.layer, self.level)
+    }
+}
+
+/// One entity or table entry: its type and the groups after it.
+struct Record<'a> {
+    kind: &'a str,
+    groups: Vec<(i32, &'a str)>,
+}
+
+impl<'a> Record<'a> {
+    fn get(&self, code: i32) -> Option<&'a str> {
+        self.groups.iter().find(|g| g.0 == code).map(|g| g.1)
+    }
+
+    /// Gets a number, which is 0 when left out as DXF allows.
+    fn number(&self, code: i32) -> Result<f64, String> {
+        match self.get(code) {
+            Some(v) => nfl::parse_number(v).map_err(|e| format!("{} in {}", e, self.kind)),
+            None => Ok(0.0),
+        }
+    }
+
+    fn layer(&self) -> &'a str {
+        self.get(8).unwrap_or("0")
+    }
+
+    /// Whether the entity's extrusion points down the z axis. Its OCS then
+    /// has x running the other way.
+    fn mirrored(&self) -> Result<bool, String> {
+        Ok(self.get(230).is_some() && self.number(230)? < 0.0)
+    }
+}
+
+/// Splits the text into (group code, value) pairs.
+fn groups(text: &str) -> Result<Vec<(i32, &str)>, String> {
+    let lines: Vec<&str> = text.lines().collect();
+
+    lines.chunks(2)
+        .enumerate()
+        .filter(|(_, pair)| !(pair.len() == 1 && pair[0].trim().is_empty()))
+        .map(|(i, pair)| {
+            let code = pair[0].trim().parse()
+                .map_err(|_| format!("Bad group code {:?} on line {}", pair[0].trim(), i * 2 + 1))?;
+            let value = pair.get(1)
+                .ok_or_else(|| format!("Missing value for group code {} at the end", code))?;
+
+            Ok((code, value.trim()))
+        })
+        .collect()
+}
+
+/// Splits the groups up into records, by section.
+fn records<'a>(groups: &[(i32, &'a str)]) -> BTreeMap<&'a str, Vec<Record<'a>>> {
+    let mut result: BTreeMap<&str, Vec<Record>> = BTreeMap::new();
+    let mut section = "";
+    let mut i = 0;
+
+    while i < groups.len() {
+        let (code, value) = groups[i];
+        i += 1;
+
+        if code != 0 {
+            continue;
+        }
+
+        let mut record = Record { kind: value, groups: Vec::new() };
+        while i < groups.len() && groups[i].0 != 0 {
+            record.groups.push(groups[i]);
+            i += 1;
+        }
+
+        match value {
+            "SECTION" => section = record.get(2).unwrap_or(""),
+            "ENDSEC" => section = "",
+            "EOF" => break,
+            _ => result.entry(section).or_default().push(record),
+        }
+    }
+
+    result
+}
+
+/// Turns an entity into curves. Entities that aren't lines or arcs give
+/// None.
+fn curves(e: &Record) -> Result<Option<Vec<Curve>>, String> {
+    let point = |x, y| -> Result<Point2D<f64, f64>, String> {
+        Ok(Point2D::new(e.number(x)?, e.number(y)?))
+    };
+
+    let result = match e.kind {
+        "LINE" => vec![Line::from_points(point(10, 20)?, point(11, 21)?).into()],
+
+        "ARC" | "CIRCLE" => {
+            let mut arc = match e.kind {
+                "ARC" => Arc::from_angles(point(10, 20)?, e.number(40)?, e.number(50)?, e.number(51)?),
+                _ => Arc::circle(point(10, 20)?, e.number(40)?),
+            };
+
+            if e.mirrored()? {
+                arc.center.x = -arc.center.x;
+                if !arc.is_circle() {
+                    let (go, end) = (arc.go_angle, arc.end_angle);
+                    arc.go_angle = 180.0 - end;
+                    arc.end_angle = 180.0 - go;
+                }
+            }
+
+            vec![normalized(arc).into()]
+        },
+
+        "LWPOLYLINE" => polyline(e)?,
+
+        _ => return Ok(None),
+    };
+
+    Ok(Some(result))
+}
+
+/// Turns a lightweight polyline into lines, and arcs where the vertices
+/// have bulges.
+fn polyline(e: &Record) -> Result<Vec<Curve>, String> {
+    let mirror = if e.mirrored()? { -1.0 } else { 1.0 };
+
+    // (point, bulge of the segment starting there)
+    let mut vertices: Vec<(Point2D<f64, f64>, f64)> = Vec::new();
+    for &(code, value) in &e.groups {
+        let number = || nfl::parse_number(value).map_err(|err| format!("{} in LWPOLYLINE", err));
+
+        match (code, vertices.last_mut()) {
+            (10, _) => vertices.push((Point2D::new(number()? * mirror, 0.0), 0.0)),
+            (20, Some(v)) => v.0.y = number()?,
+            (42, Some(v)) => v.1 = number()? * mirror,
+            _ => {},
+        }
+    }
+
+    let closed = e.get(70).and_then(|f| f.parse::<i32>().ok()).unwrap_or(0) & 1 == 1;
+    let segments = if closed { vertices.len() } else { vertices.len().saturating_sub(1) };
+
+    Ok((0..segments).map(|i| {
+        let (p1, bulge) = vertices[i];
+        let p2 = vertices[(i + 1) % vertices.len()].0;
+
+        bulge_curve(p1, p2, bulge)
+    }).collect())
+}
+
+/// The curve from p1 to p2 with the given bulge: the tangent of a quarter
+/// of the angle the arc takes up, positive if it goes counterclockwise.
+fn bulge_curve(p1: Point2D<f64, f64>, p2: Point2D<f64, f64>, bulge: f64) -> Curve {
+    let chord = p2 - p1;
+    let length = chord.length();
+
+    if bulge == 0.0 || length == 0.0 {
+        return Line::from_points(p1, p2).into();
+    }
+
+    // The center is off to the left of the chord for counterclockwise arcs
+    // under half a circle, and to the right otherwise.
+    let left = Vector2D::new(-chord.y, chord.x) / length;
+    let offset = length / 2.0 * (1.0 - bulge * bulge) / (2.0 * bulge);
+    let center = p1.lerp(p2, 0.5) + left * offset;
+    let radius = length / 2.0 * (1.0 + bulge * bulge) / (2.0 * bulge.abs());
+
+    let angle = |p: Point2D<f64, f64>| (p.y - center.y).atan2(p.x - center.x).to_degrees();
+    let (go, end) = if bulge > 0.0 { (p1, p2) } else { (p2, p1) };
+
+    normalized(Arc::from_angles(center, radius, angle(go), angle(end))).into()
+}
+
+/// Puts an arc's angles in the 0 to 360 that NFL uses; DXF angles can be
+/// anything, such as -90 or 450. Arcs that come all the way round, i.e.
+/// 90 to 450, become plain circles.
+fn normalized(arc: Arc) -> Arc {
+    if arc.is_circle() {
+        Arc::circle(arc.center, arc.radius)
+    }
+    else {
+        Arc::from_angles(arc.center, arc.radius, normalize_angle(arc.go_angle), normalize_angle(arc.end_angle))
+    }
+}
+
+/// Reads the text of an ASCII DXF file. Layers are put on levels by the
+/// table (names are matched ignoring case), then by their name if it's a
+/// number. Any others go on new levels past the highest of those, in the
+/// order they first turn up.
+pub fn read(text: &str, layers: &BTreeMap<String, u64>) -> Result<Assembly, ReadError> {
+    if text.starts_with("AutoCAD Binary DXF") {
+        return Err(ReadError::Parse(String::from("Binary DXF is not supported")));
+    }
+
+    let groups = groups(text).map_err(ReadError::Parse)?;
+    let records = records(&groups);
+    let none = Vec::new();
+
+    // Layer colors, by upper case name
+    let colors: BTreeMap<String, i32> = records.get("TABLES").unwrap_or(&none).iter()
+        .filter(|r| r.kind == "LAYER")
+        .filter_map(|r| Some((r.get(2)?.to_uppercase(), r.get(62)?.parse().ok()?)))
+        .collect();
+
+    let mut found: Vec<(&str, Curve)> = Vec::new();
+    let mut skipped: BTreeMap<&str, usize> = BTreeMap::new();
+
+    for e in records.get("ENTITIES").unwrap_or(&none) {
+        match curves(e).map_err(ReadError::Parse)? {
+            Some(curves) => found.extend(curves.into_iter().map(|c| (e.layer(), c))),
+            None => *skipped.entry(e.kind).or_default() += 1,
+        }
+    }
+
+    for (kind, count) in skipped {
+        warn!("Skipped {} {} entities; only LINE, ARC, CIRCLE and LWPOLYLINE are read", count, kind);
+    }
+
+    let table: BTreeMap<String, u64> = layers.iter()
+        .map(|(name, &level)| (name.to_uppercase(), level))
+        .collect();
+    let known = |layer: &str| {
+        table.get(&layer.to_uppercase()).copied().or_else(|| layer.parse().ok())
+    };
+
+    let mut next = found.iter()
+        .filter_map(|(layer, _)| known(layer))
+        .chain(table.values().copied())
+        .max()
+        .map_or(1, |l| l + 1);
+    let mut levels: BTreeMap<String, u64> = BTreeMap::new();
+
+    let mut result = Assembly::empty();
+    for (layer, curve) in found {
+        let name = layer.to_uppercase();
+        let level = match known(layer).or_else(|| levels.get(&name).copied()) {
+            Some(level) => level,
+            None => {
+                info!("Putting DXF layer {:?} on LEVEL/{}", layer, next);
+                levels.insert(name.clone(), next);
+                next += 1;
+                next - 1
+            },
+        };
+
+        let color = colors.get(&name).copied().and_then(Color::from_aci).map(Color::name);
+        let part: &mut Part = result.add_part(level, color.map(|c| ("COLOR", c)));
+        part.curves.push(curve);
+    }
+
+    Ok(result)
+}
+
+/// Builds up DXF text, one group at a time.
+struct Writer {
+    lines: Vec<String>,
+}
+
+impl Writer {
+    fn group(&mut self, code: i32, value: &str) -> &mut Writer {
+        self.lines.push(format!("{:>3}", code));
+        self.lines.push(String::from(value));
+        self
+    }
+
+    fn number(&mut self, code: i32, value: f64) -> &mut Writer {
+        self.group(code, &value.to_string())
+    }
+
+    fn point(&mut self, code: i32, p: Point2D<f64, f64>) -> &mut Writer {
+        self.number(code, p.x).number(code + 10, p.y)
+    }
+
+    fn layer(&mut self, name: &str, color: i32) {
+        self.group(0, "LAYER").group(2, name).group(70, "0")
+            .group(62, &color.to_string()).group(6, "CONTINUOUS");
+    }
+}
+
+/// Names the layer for a level: its name from the options if it has one,
+/// or else the level number, which reads back onto the same level.
+fn layer_name(level: u64, opts: &WriteOptions) -> String {
+    match opts.level_names.get(&level) {
+        Some(name) => name.clone(),
+        None => level.to_string(),
+    }
+}
+
+/// Writes the assembly as ASCII DXF, in the R12 layout that any CAD
+/// program can read. Each part gets a layer, colored after its COLOR/.
+/// The split points, if any are given, go on SPLIT_LAYER as POINTs.
+pub fn write(asm: &Assembly, opts: &WriteOptions, split_points: &[Point]) -> String {
+    let mut w = Writer { lines: Vec::new() };
+
+    w.group(0, "SECTION").group(2, "HEADER")
+        .group(9, "$ACADVER").group(1, "AC1009")
+        .group(0, "ENDSEC");
+
+    w.group(0, "SECTION").group(2, "TABLES");
+
+    w.group(0, "TABLE").group(2, "LTYPE").group(70, "1")
+        .group(0, "LTYPE").group(2, "CONTINUOUS").group(70, "0")
+        .group(3, "Solid line").group(72, "65").group(73, "0").number(40, 0.0)
+        .group(0, "ENDTAB");
+
+    let layers = asm.parts.len() + if split_points.is_empty() { 0 } else { 1 };
+    w.group(0, "TABLE").group(2, "LAYER").group(70, &layers.to_string());
+    for p in &asm.parts {
+        let color = p.color().map_or(DEFAULT_COLOR, Color::aci);
+        w.layer(&layer_name(p.level, opts), color);
+    }
+    if !split_points.is_empty() {
+        w.layer(SPLIT_LAYER, SPLIT_COLOR);
+    }
+    w.group(0, "ENDTAB");
+
+    w.group(0, "ENDSEC");
+
+    w.group(0, "SECTION").group(2, "ENTITIES");
+    for p in &asm.parts {
+        let layer = layer_name(p.level, opts);
+
+        for c in &p.curves {
+            match c {
+                Curve::Line(l) => {
+                    w.group(0, "LINE").group(8, &layer).point(10, l.p1()).point(11, l.p2());
+                },
+                Curve::Arc(a) if a.is_circle() => {
+                    w.group(0, "CIRCLE").group(8, &layer).point(10, a.center).number(40, a.radius);
+                },
+                Curve::Arc(a) => {
+                    w.group(0, "ARC").group(8, &layer).point(10, a.center).number(40, a.radius)
+                        .number(50, a.go_angle).number(51, a.end_angle);
+                },
+            }
+        }
+    }
+    for p in split_points {
+        w.group(0, "POINT").group(8, SPLIT_LAYER).point(10, Point2D::new(p[0], p[1]));
+    }
+    w.group(0, "ENDSEC");
+
+    w.group(0, "EOF");
+
+    w.lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dxf(entities: &[&str]) -> String {
+        let mut text = vec!["0", "SECTION", "2", "TABLES", "0", "TABLE", "2", "LAYER",
+                            "0", "LAYER", "2", "Stator", "62", "3", "0", "ENDTAB", "0", "ENDSEC",
+                            "0", "SECTION", "2", "ENTITIES"];
+        text.extend(entities);
+        text.extend(&["0", "ENDSEC", "0", "EOF"]);
+        text.join("\n")
+    }
+
+    fn close(a: Point2D<f64, f64>, b: Point2D<f64, f64>) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn layers() {
+        let text = dxf(&[
+            "0", "LINE", "8", "STATOR", "10", "0", "20", "0", "11", "1", "21", "0",
+            "0", "CIRCLE", "8", "7", "10", "0", "20", "0", "40", "2",
+            "0", "ARC", "8", "magnet", "10", "0", "20", "0", "40", "1", "50", "0", "51", "90",
+            "0", "TEXT", "8", "STATOR", "1", "hello",
+        ]);
+
+        let table = vec![(String::from("Stator"), 20)].into_iter().collect();
+        let asm = read(&text, &table).unwrap();
+        let levels: Vec<u64> = asm.parts.iter().map(|p| p.level).collect();
+
+        assert_eq!(levels, vec![7, 20, 21]);
+        assert_eq!(asm.parts[1].data.get("COLOR").map(String::as_str), Some("GREEN"));
+        assert!(asm.parts[0].arcs().next().unwrap().is_circle());
+        assert_eq!(asm.parts[2].arcs().next().unwrap().sweep(), 90.);
+    }
+
+    #[test]
+    fn polyline() {
+        // A unit square with its top edge bulged out into a half circle
+        let text = dxf(&[
+            "0", "LWPOLYLINE", "8", "1", "90", "4", "70", "1",
+            "10", "0", "20", "0",
+            "10", "1", "20", "0",
+            "10", "1", "20", "1", "42", "1",
+            "10", "0", "20", "1",
+        ]);
+
+        let asm = read(&text, &BTreeMap::new()).unwrap();
+        let curves = &asm.parts[0].curves;
+
+        assert_eq!(curves.len(), 4);
+        let arc = curves[2].as_arc().unwrap();
+        assert!(close(arc.center, Point2D::new(0.5, 1.)));
+        assert!((arc.radius - 0.5).abs() < 1e-9);
+        assert!(close(curves[2].start(), Point2D::new(1., 1.)));
+        assert!(close(curves[2].point_at(0.5), Point2D::new(0.5, 1.5)));
+
+        // Going the other way round puts the arc on the other side
+        let arc = bulge_curve(Point2D::new(1., 1.), Point2D::new(0., 1.), -1.);
+        assert!(close(arc.point_at(0.5), Point2D::new(0.5, 0.5)));
+    }
+
+    #[test]
+    fn write_read() {
+        let mut asm = Assembly::empty();
+        asm.add_part(1, vec![("COLOR", "RED")])
+            .add_line(Point2D::new(0., 0.), Point2D::new(2., 0.))
+            .add_arc(Point2D::new(1., 0.), 1., 0., 180.);
+        asm.add_part(20, vec![("COLOR", "PURPLE")])
+            .add_circle(Point2D::new(5., 5.), 0.25);
+
+        let mut opts = WriteOptions::default();
+        opts.level_names.insert(20, String::from("STATOR"));
+
+        let text = write(&asm, &opts, &[[1., 0.]]);
+        assert!(text.contains("  0\nLAYER\n  2\n1\n 70\n0\n 62\n1\n"));
+        assert!(text.contains("  0\nLAYER\n  2\nSTATOR\n 70\n0\n 62\n7\n"));
+        assert!(text.contains("  0\nPOINT\n  8\nSPLIT_POINTS\n 10\n1\n 20\n0\n"));
+
+        let table = vec![(String::from("STATOR"), 20)].into_iter().collect();
+        let again = read(&text, &table).unwrap();
+
+        assert_eq!(again.parts.len(), 2);
+        assert_eq!(again.parts[0].data.get("COLOR").map(String::as_str), Some("RED"));
+        assert_eq!(again.parts[0].curves.len(), 2);
+        assert_eq!(again.parts[0].arcs().next().unwrap().sweep(), 180.);
+        assert_eq!(again.parts[1].level, 20);
+        assert!(again.parts[1].arcs().next().unwrap().is_circle());
+    }
+
+    #[test]
+    fn angles() {
+        let text = dxf(&[
+            "0", "ARC", "8", "1", "10", "0", "20", "0", "40", "1", "50", "-90", "51", "90",
+            "0", "ARC", "8", "1", "10", "0", "20", "0", "40", "1", "50", "270", "51", "450",
+            "0", "ARC", "8", "1", "10", "0", "20", "0", "40", "1", "50", "90", "51", "450",
+            "0", "ARC", "8", "1", "10", "0", "20", "0", "40", "1", "50", "-720", "51", "-630",
+        ]);
+
+        let asm = read(&text, &BTreeMap::new()).unwrap();
+        let angles: Vec<(f64, f64)> = asm.parts[0].arcs().map(|a| (a.go_angle, a.end_angle)).collect();
+        assert_eq!(angles, vec![(270., 90.), (270., 90.), (0., 360.), (0., 90.)]);
+
+        // They're written as they are and read back the same
+        let again = read(&write(&asm, &WriteOptions::default(), &[]), &BTreeMap::new()).unwrap();
+        let sweeps: Vec<f64> = again.parts[0].arcs().map(|a| a.sweep()).collect();
+        assert_eq!(sweeps, vec![180., 180., 360., 90.]);
+        assert!(again.to_nfl(&WriteOptions::default()).contains("GOANG,270,ENDANG,90"));
+
+        // Polyline arcs get their angles from atan2, which goes negative
+        let arc = bulge_curve(Point2D::new(0., -1.), Point2D::new(0., 1.), 1.);
+        assert_eq!(arc.as_arc().map(|a| (a.go_angle, a.end_angle)), Some((270., 90.)));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(read(&dxf(&["0", "LINE", "10", "x"]), &BTreeMap::new()).is_err());
+        assert!(read("0\nSECTION\nbad\n", &BTreeMap::new()).is_err());
+        assert_eq!("STATOR=20".parse::<LayerLevel>().unwrap().level, 20);
+        assert!("STATOR".parse::<LayerLevel>().is_err());
+    }
+}
@@ -0,0 +1,185 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// NFL files come off of Windows machines, so they tend to be Windows-1252
+// with CRLF line endings and a SUB (0x1A) byte at the very end. We keep
+// track of all of that so the file can be written back the same way.
+
+use encoding_rs::WINDOWS_1252;
+use strum_macros::{EnumString, Display};
+
+pub const SUB_CHAR: u8 = 26;	// DOS end-of-file marker
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, Display)]
+#[strum(serialize_all = "kebab_case")]
+pub enum TextEncoding {
+    #[strum(to_string = "utf-8", serialize = "utf8")]
+    Utf8,
+
+    #[strum(to_string = "windows-1252", serialize = "cp1252")]
+    Windows1252,
+
+    #[strum(to_string = "latin-1", serialize = "latin1", serialize = "iso-8859-1")]
+    Latin1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, Display)]
+#[strum(serialize_all = "kebab_case")]
+pub enum LineEnding {
+    Lf,
+
+    #[strum(to_string = "crlf", serialize = "cr-lf")]
+    CrLf,
+}
+
+/// Everything about how a file is laid out below the level of NFL records.
+#[derive(Debug, Clone)]
+pub struct TextFormat {
+    pub encoding: TextEncoding,
+    pub line_ending: LineEnding,
+
+    /// Whether the file ends with a SUB byte
+    pub eof_marker: bool,
+}
+
+impl Default for TextFormat {
+    fn default() -> TextFormat {
+        TextFormat {
+            encoding: TextEncoding::Utf8,
+            line_ending: LineEnding::Lf,
+            eof_marker: false,
+        }
+    }
+}
+
+impl TextEncoding {
+    /// Picks UTF-8 if the bytes are valid UTF-8. Otherwise, we assume they
+    /// came from Windows, which is where NFL files tend to come from.
+    pub fn detect(bytes: &[u8]) -> TextEncoding {
+        if std::str::from_utf8(bytes).is_ok() {
+            TextEncoding::Utf8
+        }
+        else {
+            TextEncoding::Windows1252
+        }
+    }
+
+    /// Decodes the bytes. This never fails; bytes that are not valid in
+    /// UTF-8 are replaced. (Every byte is valid in the other two.)
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            TextEncoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            TextEncoding::Windows1252 => WINDOWS_1252.decode_without_bom_handling(bytes).0.into_owned(),
+            TextEncoding::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+        }
+    }
+
+    /// Encodes the text. Characters that can't be represented become '?'.
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        match self {
+            TextEncoding::Utf8 => text.as_bytes().to_vec(),
+            TextEncoding::Windows1252 => text.chars().flat_map(|c| {
+                let mut buf = [0u8; 4];
+                let (bytes, _, unmappable) = WINDOWS_1252.encode(c.encode_utf8(&mut buf));
+
+                if unmappable { vec![b'?'] } else { bytes.into_owned() }
+            }).collect(),
+            TextEncoding::Latin1 => text.chars()
+                .map(|c| if (c as u32) < 256 { c as u8 } else { b'?' })
+                .collect(),
+        }
+    }
+}
+
+impl LineEnding {
+    /// Goes with whatever the first line ending in the text is.
+    pub fn detect(bytes: &[u8]) -> LineEnding {
+        match bytes.iter().position(|&b| b == b'\n') {
+            Some(i) if i > 0 && bytes[i-1] == b'\r' => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+impl TextFormat {
+    /// Decodes a whole file, working out how it was laid out as we go.
+    /// The SUB byte at the end (if any) is not part of the returned text.
+    pub fn decode(bytes: &[u8], encoding: Option<TextEncoding>) -> (String, TextFormat) {
+        let eof_marker = bytes.last() == Some(&SUB_CHAR);
+        let mut end = bytes.len();
+        while end > 0 && bytes[end - 1] == SUB_CHAR {
+            end -= 1;
+        }
+        let bytes = &bytes[..end];
+
+        let format = TextFormat {
+            encoding: encoding.unwrap_or_else(|| TextEncoding::detect(bytes)),
+            line_ending: LineEnding::detect(bytes),
+            eof_marker,
+        };
+
+        (format.encoding.decode(bytes), format)
+    }
+
+    /// Encodes text with '\n' line endings in this format.
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        let mut text = text.replace('\n', self.line_ending.as_str());
+
+        // The SUB goes on a line of its own
+        if self.eof_marker && !text.is_empty() && !text.ends_with('\n') {
+            text.push_str(self.line_ending.as_str());
+        }
+
+        let mut result = self.encoding.encode(&text);
+        if self.eof_marker {
+            result.push(SUB_CHAR);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_file() {
+        let bytes = b"PARTNO/'caf\xe9 \x80',1\r\nFINI/\r\nSTOP/\r\n\x1a";
+        let (text, format) = TextFormat::decode(bytes, None);
+
+        assert_eq!(text, "PARTNO/'caf\u{e9} \u{20ac}',1\r\nFINI/\r\nSTOP/\r\n");
+        assert_eq!(format.encoding, TextEncoding::Windows1252);
+        assert_eq!(format.line_ending, LineEnding::CrLf);
+        assert!(format.eof_marker);
+
+        let text: Vec<&str> = text.lines().collect();
+        assert_eq!(format.encode(&text.join("\n")), bytes.to_vec());
+    }
+
+    #[test]
+    fn latin1() {
+        let (text, _) = TextFormat::decode(b"\x80\xff", Some(TextEncoding::Latin1));
+        assert_eq!(text, "\u{80}\u{ff}");
+        assert_eq!(TextEncoding::Latin1.encode("\u{ff}\u{20ac}"), b"\xff?");
+    }
+}
@@ -0,0 +1,295 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// Writes assemblies as GeoGebra files. A .ggb file is a zip archive, and
+// the only thing in it GeoGebra needs is geogebra.xml, which holds the
+// construction: a list of free objects and the commands that build the
+// rest from them.
+//
+// Every distinct point becomes a free point, and lines, arcs and circles
+// are built from those with Segment, CircularArc and Circle, so dragging a
+// point in GeoGebra moves everything that ends there. Points are drawn
+// small so they don't hide the curves, and split points bigger and in
+// orange. Objects are captioned with the label they get in the NFL output.
+
+use std::io::{Cursor, Write};
+use euclid::Point2D;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+use super::Assembly;
+use super::color::Color;
+use super::nfl::{self, LabelScheme, Labeler, WriteOptions};
+use super::part::curve::Curve;
+use super::points::PointSet;
+use super::report::Point;
+use super::draw::{escape, view_bounds};
+
+const DEFAULT_COLOR: [u8; 3] = [0, 0, 0];
+const SPLIT_COLOR: [u8; 3] = [255, 128, 0];
+
+// Sizes points are drawn at, in GeoGebra's units
+const POINT_SIZE: u32 = 2;
+const SPLIT_POINT_SIZE: u32 = 5;
+
+// Size in pixels of the view the whole assembly is fitted into
+const VIEW_WIDTH: f64 = 1200.0;
+const VIEW_HEIGHT: f64 = 700.0;
+
+/// Gets the color to draw a part in, after its COLOR/, as RGB. GeoGebra
+/// has a white background, so these are darker than the usual CAD colors
+/// and white is drawn black.
+fn color(color: Option<Color>) -> [u8; 3] {
+    match color {
+        Some(Color::Red) => [204, 0, 0],
+        Some(Color::Yellow) => [204, 153, 0],
+        Some(Color::Green) => [0, 153, 0],
+        Some(Color::Cyan) => [0, 153, 153],
+        Some(Color::Blue) => [0, 0, 204],
+        Some(Color::Magenta) => [153, 0, 153],
+        Some(Color::White) => [0, 0, 0],
+        None => DEFAULT_COLOR,
+    }
+}
+
+/// Builds up the construction in geogebra.xml.
+struct GgbWriter {
+    xml: Vec<String>,
+
+    // Point name by position. Points within the point tolerance are the
+    // same point, so the ends of arcs meet the lines they touch.
+    points: PointSet<String>,
+    next_point: u64,
+}
+
+impl GgbWriter {
+    fn new(point_tolerance: f64) -> GgbWriter {
+        GgbWriter { xml: Vec::new(), points: PointSet::new(point_tolerance), next_point: 1 }
+    }
+
+    /// Gets the name of the point at p, or of one within the point
+    /// tolerance of it, creating it if needed.
+    fn point(&mut self, p: Point2D<f64, f64>, split: bool) -> String {
+        if let Some(name) = self.points.get(p) {
+            return name.clone();
+        }
+
+        let name = format!("{}_{{{}}}", if split { 'S' } else { 'P' }, self.next_point);
+        self.next_point += 1;
+        self.points.insert(p, name.clone());
+
+        self.xml.push(format!("<expression label=\"{}\" exp=\"({}, {})\" type=\"point\"/>", name, p.x, p.y));
+        self.xml.push(format!("<element type=\"point\" label=\"{}\">", name));
+        self.xml.push(String::from("\t<show object=\"true\" label=\"false\"/>"));
+        if split {
+            self.xml.push(obj_color(SPLIT_COLOR));
+        }
+        self.xml.push(format!("\t<coords x=\"{}\" y=\"{}\" z=\"1\"/>", p.x, p.y));
+        self.xml.push(format!("\t<pointSize val=\"{}\"/>", if split { SPLIT_POINT_SIZE } else { POINT_SIZE }));
+        self.xml.push(String::from("</element>"));
+
+        name
+    }
+
+    /// Adds a curve, built from its points, named and captioned with its
+    /// label.
+    fn curve(&mut self, c: &Curve, name: &str, caption: &str, color: [u8; 3]) {
+        let (command, element, inputs) = match c {
+            Curve::Line(l) => {
+                ("Segment", "segment", vec![self.point(l.p1(), false), self.point(l.p2(), false)])
+            },
+            Curve::Arc(a) if a.is_circle() => {
+                ("Circle", "conic", vec![self.point(a.center, false), a.radius.to_string()])
+            },
+            Curve::Arc(a) => {
+                let points = vec![self.point(a.center, false), self.point(a.start(), false), self.point(a.end(), false)];
+                ("CircularArc", "conicpart", points)
+            },
+        };
+
+        let inputs: Vec<String> = inputs.iter().enumerate()
+            .map(|(i, input)| format!("a{}=\"{}\"", i, input))
+            .collect();
+
+        self.xml.push(format!("<command name=\"{}\">", command));
+        self.xml.push(format!("\t<input {}/>", inputs.join(" ")));
+        self.xml.push(format!("\t<output a0=\"{}\"/>", name));
+        self.xml.push(String::from("</command>"));
+
+        self.xml.push(format!("<element type=\"{}\" label=\"{}\">", element, name));
+        self.xml.push(String::from("\t<show object=\"true\" label=\"false\"/>"));
+        self.xml.push(obj_color(color));
+        self.xml.push(String::from("\t<labelMode val=\"3\"/>"));
+        self.xml.push(format!("\t<caption val=\"{}\"/>", escape(caption)));
+        self.xml.push(String::from("\t<lineStyle thickness=\"5\" type=\"0\" typeHidden=\"1\"/>"));
+        self.xml.push(String::from("</element>"));
+    }
+}
+
+fn obj_color(c: [u8; 3]) -> String {
+    format!("\t<objColor r=\"{}\" g=\"{}\" b=\"{}\" alpha=\"0\"/>", c[0], c[1], c[2])
+}
+
+/// Gets the GeoGebra name for a curve, i.e. "L_{00017}". Names have to be
+/// unique, so with per-level labels the level goes in too ("L20_{00017}").
+/// A plain "L00017" would be taken for a spreadsheet cell.
+fn object_name(label: &str, level: u64, opts: &WriteOptions) -> String {
+    let (prefix, digits) = label.split_at(1);
+
+    match opts.label_scheme {
+        LabelScheme::PerLevel => format!("{}{}_{{{}}}", prefix, level, digits),
+        _ => format!("{}_{{{}}}", prefix, digits),
+    }
+}
+
+/// Builds geogebra.xml for the assembly. The view is set up so the whole
+/// assembly fits in it.
+fn geogebra_xml(asm: &Assembly, opts: &WriteOptions, split_points: &[Point]) -> String {
+    let mut w = GgbWriter::new(opts.point_tolerance);
+
+    for p in split_points {
+        w.point(Point2D::new(p[0], p[1]), true);
+    }
+
+    let mut labeler = Labeler::new(opts.label_scheme);
+    for p in &asm.parts {
+        let color = color(p.color());
+
+        for c in &p.curves {
+            let label = nfl::format_label(c.prefix(), labeler.next(c.prefix(), p.level), opts);
+            w.curve(c, &object_name(&label, p.level, opts), &label, color);
+        }
+    }
+
+    let bounds = view_bounds(asm);
+    let scale = (VIEW_WIDTH / bounds.width()).min(VIEW_HEIGHT / bounds.height());
+    let x_zero = (VIEW_WIDTH - bounds.width() * scale) / 2.0 - bounds.min.x * scale;
+    let y_zero = (VIEW_HEIGHT - bounds.height() * scale) / 2.0 + bounds.max.y * scale;
+
+    let mut result = vec![
+        String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>"),
+        String::from("<geogebra format=\"5.0\">"),
+        String::from("<euclidianView>"),
+        format!("\t<size width=\"{}\" height=\"{}\"/>", VIEW_WIDTH, VIEW_HEIGHT),
+        format!("\t<coordSystem xZero=\"{}\" yZero=\"{}\" scale=\"{}\" yscale=\"{}\"/>",
+                x_zero, y_zero, scale, scale),
+        String::from("</euclidianView>"),
+        String::from("<kernel>"),
+        String::from("\t<decimals val=\"13\"/>"),
+        String::from("\t<angleUnit val=\"degree\"/>"),
+        String::from("</kernel>"),
+        String::from("<construction title=\"\" author=\"\" date=\"\">"),
+    ];
+    result.append(&mut w.xml);
+    result.push(String::from("</construction>"));
+    result.push(String::from("</geogebra>"));
+
+    result.join("\n") + "\n"
+}
+
+/// Writes the assembly as a GeoGebra .ggb file. Each part is drawn in its
+/// COLOR/, and the given split points are shown as points.
+pub fn write(asm: &Assembly, opts: &WriteOptions, split_points: &[Point]) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    // Nothing here can fail when writing to memory
+    zip.start_file("geogebra.xml", SimpleFileOptions::default()).expect("Zip entries can be started");
+    zip.write_all(geogebra_xml(asm, opts, split_points).as_bytes()).expect("Writing to memory works");
+
+    zip.finish().expect("Zip files can be finished").into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    #[test]
+    fn ggb() {
+        let mut asm = Assembly::empty();
+        asm.add_part(1, vec![("COLOR", "GREEN")])
+            .add_line(Point2D::new(0., 0.), Point2D::new(1., 0.))
+            .add_line(Point2D::new(1., 0.), Point2D::new(2., 0.))
+            .add_arc(Point2D::new(0., 0.), 2., 0., 90.);
+        asm.add_part(2, vec![("COLOR", "1")])
+            .add_circle(Point2D::new(5., 5.), 0.5);
+
+        let bytes = write(&asm, &WriteOptions::default(), &[[1., 0.]]);
+
+        let mut xml = String::new();
+        ZipArchive::new(Cursor::new(bytes)).unwrap()
+            .by_name("geogebra.xml").unwrap()
+            .read_to_string(&mut xml).unwrap();
+
+        // The split point comes first and is shared by both lines
+        assert!(xml.contains("<expression label=\"S_{1}\" exp=\"(1, 0)\" type=\"point\"/>"));
+        assert!(xml.contains("<input a0=\"P_{2}\" a1=\"S_{1}\"/>"));
+        assert!(xml.contains("<input a0=\"S_{1}\" a1=\"P_{3}\"/>"));
+
+        assert!(xml.contains("<output a0=\"L_{00001}\"/>"));
+        assert!(xml.contains("<caption val=\"C00003\"/>"));
+        assert!(xml.contains("<command name=\"CircularArc\">\n\t<input a0=\"P_{2}\" a1=\"P_{3}\" a2=\"P_{4}\"/>"));
+        assert!(xml.contains("<command name=\"Circle\">\n\t<input a0=\"P_{5}\" a1=\"0.5\"/>"));
+        assert!(xml.contains("<objColor r=\"204\" g=\"0\" b=\"0\" alpha=\"0\"/>"));
+    }
+
+    #[test]
+    fn per_type_labels() {
+        // A quarter circle closed off by two lines; all three share points
+        let mut asm = Assembly::empty();
+        asm.add_part(1, vec![("COLOR", "GREEN")])
+            .add_line(Point2D::new(0., 0.), Point2D::new(1., 0.))
+            .add_arc(Point2D::new(0., 0.), 1., 0., 90.)
+            .add_line(Point2D::new(0., 1.), Point2D::new(0., 0.));
+
+        let opts = WriteOptions { label_scheme: LabelScheme::PerType, ..WriteOptions::default() };
+        let xml = geogebra_xml(&asm, &opts, &[]);
+
+        assert!(xml.contains("<command name=\"Segment\">\n\t<input a0=\"P_{1}\" a1=\"P_{2}\"/>\n\t<output a0=\"L_{00001}\"/>"));
+        assert!(xml.contains("<command name=\"CircularArc\">\n\t<input a0=\"P_{1}\" a1=\"P_{2}\" a2=\"P_{3}\"/>\n\t<output a0=\"C_{00001}\"/>"));
+        assert!(xml.contains("<command name=\"Segment\">\n\t<input a0=\"P_{3}\" a1=\"P_{1}\"/>\n\t<output a0=\"L_{00002}\"/>"));
+        assert_eq!(xml.matches("type=\"point\"/>").count(), 3);
+        assert!(xml.contains("<caption val=\"C00001\"/>"));
+    }
+
+    #[test]
+    fn nearly_equal_points() {
+        // sin(30°) comes out as 0.49999999999999994
+        let end = Point2D::new(30f64.to_radians().cos(), 0.5);
+
+        let mut asm = Assembly::empty();
+        asm.add_part(1, vec![("COLOR", "GREEN")])
+            .add_line(Point2D::new(0., 0.), end)
+            .add_arc(Point2D::new(0., 0.), 1., 0., 30.);
+
+        // The arc ends on the line's end point
+        let xml = geogebra_xml(&asm, &WriteOptions::default(), &[]);
+        assert_eq!(xml.matches("type=\"point\"/>").count(), 3);
+        assert!(xml.contains("<input a0=\"P_{1}\" a1=\"P_{3}\" a2=\"P_{2}\"/>"));
+
+        // Unless they have to be exactly equal
+        let opts = WriteOptions { point_tolerance: 0.0, ..WriteOptions::default() };
+        assert_eq!(geogebra_xml(&asm, &opts, &[]).matches("type=\"point\"/>").count(), 4);
+    }
+
+    #[test]
+    fn names() {
+        let mut opts = WriteOptions::default();
+        assert_eq!(object_name("L00017", 20, &opts), "L_{00017}");
+
+        opts.label_scheme = LabelScheme::PerLevel;
+        assert_eq!(object_name("C00017", 20, &opts), "C20_{00017}");
+    }
+}
@@ -0,0 +1,217 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// Picking which parts to keep, and moving parts to other levels.
+//
+// Exports tend to have construction and annotation levels that have no
+// business in an FE model, and sometimes one piece of the model ends up
+// spread over several levels.
+
+use std::fmt;
+use std::str::FromStr;
+use super::part::Part;
+
+/// A set of levels, written like "1,2,10-20".
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelSet {
+    ranges: Vec<(u64, u64)>,
+}
+
+/// A test on a part's data, written like "COLOR=3". Just "COLOR" matches
+/// any part that has a COLOR at all.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttrMatch {
+    key: String,
+    value: Option<String>,
+}
+
+/// Moves parts on some levels to another one, written like "38=20" or
+/// "30-39=20".
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remap {
+    pub from: LevelSet,
+    pub to: u64,
+}
+
+/// Decides which parts are kept.
+#[derive(Debug, Clone, Default)]
+pub struct LevelFilter {
+    /// If given, only these levels are kept
+    pub include: Option<LevelSet>,
+    pub exclude: Option<LevelSet>,
+
+    /// If any are given, only parts matching one of them are kept
+    pub include_attrs: Vec<AttrMatch>,
+    pub exclude_attrs: Vec<AttrMatch>,
+}
+
+impl LevelSet {
+    pub fn contains(&self, level: u64) -> bool {
+        self.ranges.iter().any(|&(lo, hi)| lo <= level && level <= hi)
+    }
+}
+
+impl FromStr for LevelSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LevelSet, String> {
+        let bad = || format!("Bad level list {:?}; expected something like \"1,2,10-20\"", s);
+        let mut ranges = Vec::new();
+
+        for item in s.split(',').map(str::trim) {
+            let (lo, hi) = match item.split_once('-') {
+                Some((lo, hi)) => (lo.trim(), hi.trim()),
+                None => (item, item),
+            };
+
+            let lo: u64 = lo.parse().map_err(|_| bad())?;
+            let hi: u64 = hi.parse().map_err(|_| bad())?;
+            if lo > hi {
+                return Err(bad());
+            }
+
+            ranges.push((lo, hi));
+        }
+
+        Ok(LevelSet { ranges })
+    }
+}
+
+impl fmt::Display for LevelSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let items: Vec<String> = self.ranges.iter()
+            .map(|&(lo, hi)| if lo == hi { lo.to_string() } else { format!("{}-{}", lo, hi) })
+            .collect();
+
+        write!(f, "{}", items.join(","))
+    }
+}
+
+impl AttrMatch {
+    /// Keys and values are matched ignoring case and surrounding whitespace.
+    pub fn matches(&self, part: &Part) -> bool {
+        part.data.iter().any(|(k, v)| {
+            k.trim().eq_ignore_ascii_case(&self.key)
+                && self.value.as_ref().is_none_or(|value| v.trim().eq_ignore_ascii_case(value))
+        })
+    }
+}
+
+impl FromStr for AttrMatch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AttrMatch, String> {
+        let (key, value) = match s.split_once('=') {
+            Some((k, v)) => (k, Some(v.trim().to_string())),
+            None => (s, None),
+        };
+
+        // Allow "COLOR/" as it appears in the file
+        let key = key.trim().trim_end_matches('/');
+        if key.is_empty() {
+            return Err(format!("Bad attribute {:?}; expected something like \"COLOR=3\"", s));
+        }
+
+        Ok(AttrMatch { key: key.to_string(), value })
+    }
+}
+
+impl fmt::Display for AttrMatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.value {
+            Some(v) => write!(f, "{}={}", self.key, v),
+            None => write!(f, "{}", self.key),
+        }
+    }
+}
+
+impl FromStr for Remap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Remap, String> {
+        let bad = || format!("Bad remap {:?}; expected something like \"38=20\"", s);
+        let (from, to) = s.split_once('=').ok_or_else(bad)?;
+
+        Ok(Remap {
+            from: from.parse().map_err(|_| bad())?,
+            to: to.trim().parse().map_err(|_| bad())?,
+        })
+    }
+}
+
+impl fmt::Display for Remap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.from, self.to)
+    }
+}
+
+impl LevelFilter {
+    pub fn keeps(&self, part: &Part) -> bool {
+        self.include.as_ref().is_none_or(|s| s.contains(part.level))
+            && !self.exclude.as_ref().is_some_and(|s| s.contains(part.level))
+            && (self.include_attrs.is_empty() || self.include_attrs.iter().any(|a| a.matches(part)))
+            && !self.exclude_attrs.iter().any(|a| a.matches(part))
+    }
+
+    /// Whether the filter would keep everything
+    pub fn is_empty(&self) -> bool {
+        self.include.is_none() && self.exclude.is_none()
+            && self.include_attrs.is_empty() && self.exclude_attrs.is_empty()
+    }
+}
+
+/// Works out which level a part ends up on. The first remap that applies
+/// wins; remaps aren't chained.
+pub fn remapped(level: u64, remaps: &[Remap]) -> u64 {
+    remaps.iter()
+        .find(|r| r.from.contains(level))
+        .map_or(level, |r| r.to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_sets() {
+        let set: LevelSet = "1, 2,10-20".parse().unwrap();
+
+        assert!(set.contains(1) && set.contains(10) && set.contains(20));
+        assert!(!set.contains(3) && !set.contains(21));
+        assert_eq!(set.to_string(), "1,2,10-20");
+
+        assert!("20-10".parse::<LevelSet>().is_err());
+        assert!("1,,2".parse::<LevelSet>().is_err());
+    }
+
+    #[test]
+    fn filters() {
+        let part = |level, color: &str| Part::new(level, vec![format!("COLOR/{}", color)]).unwrap();
+        let filter = LevelFilter {
+            exclude: Some("38".parse().unwrap()),
+            exclude_attrs: vec!["COLOR/=7".parse().unwrap()],
+            ..LevelFilter::default()
+        };
+
+        assert!(filter.keeps(&part(20, "3")));
+        assert!(!filter.keeps(&part(38, "3")));
+        assert!(!filter.keeps(&part(20, "7")));
+
+        let remaps = vec!["38=20".parse().unwrap(), "30-39=30".parse().unwrap()];
+        assert_eq!(remapped(38, &remaps), 20);
+        assert_eq!(remapped(35, &remaps), 30);
+        assert_eq!(remapped(1, &remaps), 1);
+    }
+}
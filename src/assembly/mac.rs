@@ -0,0 +1,195 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// Writes assemblies out as ANSYS APDL macros.
+//
+// Every distinct point becomes a keypoint, lines become L commands and arcs
+// become LARC commands. Each part ends up in a line component named after
+// its level, unless it's been given a name of its own. Keypoints and lines
+// are numbered from 1, so the macro expects to be run on an empty model.
+
+use euclid::Point2D;
+use super::Assembly;
+use super::part::arc::Arc;
+use super::part::curve::Curve;
+use super::nfl::WriteOptions;
+use super::points::PointSet;
+
+// LARC can't make arcs of 180 degrees or more, so bigger ones get broken
+// up into pieces no bigger than this.
+const MAX_ARC_SWEEP: f64 = 90.0;
+
+const COMMENT: &str = "!";
+
+struct MacWriter<'a> {
+    opts: &'a WriteOptions,
+    result: String,
+
+    // Points closer together than opts.point_tolerance are the same
+    // keypoint
+    keypoints: PointSet<u64>,
+    next_keypoint: u64,
+    next_line: u64,
+}
+
+impl<'a> MacWriter<'a> {
+    fn new(opts: &'a WriteOptions) -> MacWriter<'a> {
+        MacWriter {
+            opts,
+            result: String::new(),
+            keypoints: PointSet::new(opts.point_tolerance),
+            next_keypoint: 1,
+            next_line: 1,
+        }
+    }
+
+    fn push(&mut self, command: String) {
+        self.result.push_str(&command);
+        self.result.push('\n');
+    }
+
+    /// Gets the keypoint at the given point, creating it if needed.
+    fn keypoint(&mut self, p: Point2D<f64, f64>) -> u64 {
+        if let Some(&k) = self.keypoints.get(p) {
+            return k;
+        }
+
+        let k = self.temp_keypoint(p);
+        self.keypoints.insert(p, k);
+        k
+    }
+
+    /// Creates a keypoint that won't be shared with anything else.
+    fn temp_keypoint(&mut self, p: Point2D<f64, f64>) -> u64 {
+        let k = self.next_keypoint;
+        self.next_keypoint += 1;
+
+        let command = format!("K,{},{},{}",
+            k, self.opts.number.format(p.x), self.opts.number.format(p.y));
+        self.push(command);
+
+        k
+    }
+
+    fn line(&mut self, p1: Point2D<f64, f64>, p2: Point2D<f64, f64>) {
+        let k1 = self.keypoint(p1);
+        let k2 = self.keypoint(p2);
+
+        self.push(format!("L,{},{}", k1, k2));
+        self.next_line += 1;
+    }
+
+    fn arc(&mut self, arc: &Arc) {
+        let sweep = arc.sweep();
+        let pieces = (sweep / MAX_ARC_SWEEP).ceil().max(1.0) as usize;
+        let step = sweep / pieces as f64;
+
+        // The center is only needed to tell LARC which way the arc bends,
+        // so it gets deleted once we're done with it.
+        let center = self.temp_keypoint(arc.center);
+        let radius = self.opts.number.format(arc.radius);
+
+        let mut start = self.keypoint(arc.point_at_angle(arc.go_angle));
+        for i in 1..=pieces {
+            let end = self.keypoint(arc.point_at_angle(arc.go_angle + step * i as f64));
+
+            self.push(format!("LARC,{},{},{},{}", start, end, center, radius));
+            self.next_line += 1;
+
+            start = end;
+        }
+
+        self.push(format!("KDELE,{}", center));
+    }
+}
+
+/// Converts the assembly to an APDL macro.
+pub fn write(asm: &Assembly, opts: &WriteOptions) -> String {
+    let mut writer = MacWriter::new(opts);
+
+    writer.push(format!("{} Generated by nfl2mac from:", COMMENT));
+    for l in &asm.header {
+        writer.push(format!("{}   {}", COMMENT, l));
+    }
+    writer.push(String::from("/PREP7"));
+
+    for p in &asm.parts {
+        writer.push(String::from(COMMENT));
+        writer.push(format!("{} LEVEL/{}", COMMENT, p.level));
+
+        let first_line = writer.next_line;
+
+        for c in &p.curves {
+            match c {
+                Curve::Line(l) => writer.line(l.p1(), l.p2()),
+                Curve::Arc(a) => writer.arc(a),
+            }
+        }
+
+        if writer.next_line > first_line {
+            writer.push(format!("LSEL,S,LINE,,{},{}", first_line, writer.next_line - 1));
+            let name = match opts.level_names.get(&p.level) {
+                Some(name) => name.clone(),
+                None => format!("LEVEL_{}", p.level),
+            };
+            writer.push(format!("CM,{},LINE", name));
+        }
+    }
+
+    writer.push(String::from(COMMENT));
+    writer.push(String::from("ALLSEL"));
+
+    writer.result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(asm: &Assembly) -> Vec<String> {
+        write(asm, &WriteOptions::default()).lines()
+            .filter(|l| !l.starts_with(COMMENT))
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn arc_meets_line() {
+        let mut asm = Assembly::empty();
+        asm.add_part(1, vec![("COLOR", "GREEN")])
+            .add_line(Point2D::new(0., 0.), Point2D::new(0., 1.))
+            .add_arc(Point2D::new(0., 0.), 1., 0., 90.);
+
+        let commands = commands(&asm);
+        assert!(commands.contains(&String::from("K,2,0,1")));
+        assert!(commands.contains(&String::from("LARC,4,2,3,1")));
+        assert_eq!(commands.iter().filter(|c| c.starts_with("K,")).count(), 4);
+    }
+
+    #[test]
+    fn nearly_equal_keypoints() {
+        // sin(30°) comes out as 0.49999999999999994
+        let end = Point2D::new(30f64.to_radians().cos(), 0.5);
+
+        let mut asm = Assembly::empty();
+        asm.add_part(1, vec![("COLOR", "GREEN")])
+            .add_line(Point2D::new(0., 0.), end)
+            .add_arc(Point2D::new(0., 0.), 1., 0., 30.);
+
+        let commands = commands(&asm);
+        assert!(commands.contains(&String::from("LARC,4,2,3,1")));
+        assert_eq!(commands.iter().filter(|c| c.starts_with("K,")).count(), 4);
+    }
+}
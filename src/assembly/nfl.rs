@@ -0,0 +1,476 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// Helpers shared by everything that reads or writes NFL records.
+//
+// NFL (like APT, which it is derived from) allows a record to be broken
+// over several physical lines by ending each line with a '$'. The CAD
+// system refuses lines longer than 79 columns, so every writer needs to
+// wrap its records the same way the original exporter does.
+
+use std::collections::{HashMap, BTreeMap};
+use strum_macros::{EnumString, Display};
+use super::encoding::TextFormat;
+
+pub const DEFAULT_MAX_LINE_LEN: usize = 79;
+pub const DEFAULT_LABEL_WIDTH: usize = 5;
+pub const DEFAULT_POINT_TOLERANCE: f64 = 0.000001;
+
+const LINE_ESCAPE: char = '$';
+const COMMENT_START: &str = "$$";
+const DELIM: char = ',';
+const QUOTE: char = '\'';
+
+// This is what the original exporter puts in front of continued lines
+const CONTINUATION_INDENT: &str = "              ";
+
+/// Options that control how records are written out.
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    /// Maximum length of a physical line, including the trailing '$'
+    pub max_line_len: usize,
+
+    /// How coordinates, radii, angles, etc. are written
+    pub number: NumberFormat,
+
+    /// How entities are numbered
+    pub label_scheme: LabelScheme,
+
+    /// Number of digits in entity labels (i.e. 5 gives "L00001")
+    pub label_width: usize,
+
+    /// Encoding, line endings and EOF marker of the written file
+    pub text: TextFormat,
+
+    /// Names to use for levels in formats that name them, such as the
+    /// components in an APDL macro
+    pub level_names: BTreeMap<u64, String>,
+
+    /// How close points have to be to count as the same point, in formats
+    /// that share points between curves, such as the keypoints in an APDL
+    /// macro
+    pub point_tolerance: f64,
+}
+
+impl Default for WriteOptions {
+    fn default() -> WriteOptions {
+        WriteOptions {
+            max_line_len: DEFAULT_MAX_LINE_LEN,
+            number: NumberFormat::default(),
+            label_scheme: LabelScheme::Global,
+            label_width: DEFAULT_LABEL_WIDTH,
+            text: TextFormat::default(),
+            level_names: BTreeMap::new(),
+            point_tolerance: DEFAULT_POINT_TOLERANCE,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// The biggest number that fits in a label
+    pub fn max_label(&self) -> u64 {
+        10u64.saturating_pow(self.label_width as u32) - 1
+    }
+}
+
+/// Which entities share a counter when labelling them.
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, Display)]
+#[strum(serialize_all = "kebab_case")]
+pub enum LabelScheme {
+    /// Every entity shares one counter, like the original exporter
+    Global,
+
+    /// Lines and arcs are counted separately (L00001 and C00001 can both exist)
+    PerType,
+
+    /// Every level starts counting from 1 again
+    PerLevel,
+}
+
+impl LabelScheme {
+    /// Works out which scheme a set of labels was written with, given the
+    /// level each label was found on.
+    pub fn detect<'a, I>(labels: I) -> LabelScheme
+    where I: IntoIterator<Item = (u64, &'a str)>
+    {
+        // Number -> (prefix, level) where that number was first seen
+        let mut seen: HashMap<u64, (char, u64)> = HashMap::new();
+        let mut result = LabelScheme::Global;
+
+        for (level, label) in labels {
+            if let Some((prefix, n)) = parse_label(label) {
+                match seen.get(&n) {
+                    Some(&(p, l)) if p == prefix && l != level => {
+                        return LabelScheme::PerLevel;
+                    },
+                    Some(&(p, _)) if p != prefix => {
+                        result = LabelScheme::PerType;
+                    },
+                    Some(_) => {},
+                    None => {
+                        seen.insert(n, (prefix, level));
+                    },
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Hands out entity numbers according to a `LabelScheme`.
+#[derive(Debug)]
+pub struct Labeler {
+    scheme: LabelScheme,
+    counters: HashMap<(char, u64), u64>,
+    max_issued: u64,
+}
+
+impl Labeler {
+    pub fn new(scheme: LabelScheme) -> Labeler {
+        Labeler { scheme, counters: HashMap::new(), max_issued: 0 }
+    }
+
+    /// Gets the number for the next entity with the given label prefix
+    /// ('L' or 'C') on the given level. Numbers start at 1.
+    pub fn next(&mut self, prefix: char, level: u64) -> u64 {
+        let key = match self.scheme {
+            LabelScheme::Global => (' ', 0),
+            LabelScheme::PerType => (prefix, 0),
+            LabelScheme::PerLevel => (' ', level),
+        };
+
+        let counter = self.counters.entry(key).or_insert(0);
+        *counter += 1;
+        self.max_issued = self.max_issued.max(*counter);
+
+        *counter
+    }
+
+    /// The biggest number handed out so far
+    pub fn max_issued(&self) -> u64 {
+        self.max_issued
+    }
+}
+
+/// Formats a label, i.e. ('L', 17) with a width of 5 gives "L00017".
+pub fn format_label(prefix: char, id: u64, opts: &WriteOptions) -> String {
+    format!("{}{:0>width$}", prefix, id, width = opts.label_width)
+}
+
+/// Splits a label like "L00017" into its prefix and number.
+/// Labels can be any width.
+pub fn parse_label(label: &str) -> Option<(char, u64)> {
+    let prefix = label.chars().next()?;
+    let number = label[prefix.len_utf8()..].trim().parse().ok()?;
+
+    Some((prefix, number))
+}
+
+/// Gets the label from the front of a record (the part before the '='),
+/// if it has one. A blank label counts as none.
+pub fn record_label(record: &str) -> Option<String> {
+    let label = record[..record.find('=')?].trim();
+    if label.is_empty() { None } else { Some(String::from(label)) }
+}
+
+/// Controls how numbers are written.
+///
+/// The defaults give the same thing as Rust's `{}`, which is the shortest
+/// string that reads back as the same f64.
+#[derive(Debug, Clone)]
+pub struct NumberFormat {
+    /// Round to this many significant digits. Trailing zeros are dropped.
+    pub sig_digits: Option<usize>,
+
+    /// Write whole numbers as "120." instead of "120", like the exporter does
+    pub trailing_dot: bool,
+
+    /// Numbers with a decimal exponent below this use scientific notation
+    pub sci_min_exp: i32,
+
+    /// Numbers with a decimal exponent above this use scientific notation
+    pub sci_max_exp: i32,
+}
+
+impl Default for NumberFormat {
+    fn default() -> NumberFormat {
+        NumberFormat {
+            sig_digits: None,
+            trailing_dot: false,
+            sci_min_exp: i32::MIN,
+            sci_max_exp: i32::MAX,
+        }
+    }
+}
+
+impl NumberFormat {
+    pub fn format(&self, value: f64) -> String {
+        if !value.is_finite() {
+            return value.to_string();
+        }
+
+        // Letting Rust do the rounding in scientific notation means we only
+        // round once, and we get the digits and exponent separately.
+        let sci = match self.sig_digits {
+            Some(n) => format!("{:.*e}", n.max(1) - 1, value),
+            None => format!("{:e}", value),
+        };
+
+        let (mantissa, exp) = sci.split_at(sci.find('e').unwrap());
+        let exp: i32 = exp[1..].parse().unwrap();
+        let negative = mantissa.starts_with('-');
+        let digits: String = mantissa.chars().filter(|c| c.is_ascii_digit()).collect();
+        let digits = digits.trim_end_matches('0');
+
+        // This also takes care of -0
+        if digits.is_empty() {
+            return self.join("0", "", None);
+        }
+
+        let sign = if negative { "-" } else { "" };
+
+        if exp < self.sci_min_exp || exp > self.sci_max_exp {
+            let whole = format!("{}{}", sign, &digits[..1]);
+            return self.join(&whole, &digits[1..], Some(exp));
+        }
+
+        if exp < 0 {
+            let frac = format!("{}{}", "0".repeat((-exp - 1) as usize), digits);
+            self.join(&format!("{}0", sign), &frac, None)
+        }
+        else {
+            let point = exp as usize + 1;
+            if digits.len() > point {
+                self.join(&format!("{}{}", sign, &digits[..point]), &digits[point..], None)
+            }
+            else {
+                let whole = format!("{}{}{}", sign, digits, "0".repeat(point - digits.len()));
+                self.join(&whole, "", None)
+            }
+        }
+    }
+
+    fn join(&self, whole: &str, frac: &str, exp: Option<i32>) -> String {
+        let mut result = String::from(whole);
+
+        if !frac.is_empty() {
+            result.push('.');
+            result.push_str(frac);
+        }
+        else if self.trailing_dot {
+            result.push('.');
+        }
+
+        if let Some(exp) = exp {
+            result.push_str(&format!("E{}", exp));
+        }
+
+        result
+    }
+}
+
+/// Builds a record out of its head (everything up to and including the '/',
+/// i.e. "L00001=LINE/") and its fields, wrapping it as needed.
+///
+/// Fields are joined with commas. A field is never broken up, so callers can
+/// keep things like "GOANG,120." together by passing them as one field.
+/// If a single field is too long to fit on a line, it will overflow.
+pub fn write_record(head: &str, fields: &[String], opts: &WriteOptions) -> String {
+    let mut result = String::from(head);
+    let mut line_len = head.len();
+
+    // Whether anything besides the head/indentation is on the current line.
+    // We never break a line that has nothing on it; that would just loop.
+    let mut line_used = false;
+
+    for (i, field) in fields.iter().enumerate() {
+        let last = i + 1 == fields.len();
+
+        // If this isn't the last field, we need room for the comma and also
+        // the '$' that might follow it.
+        let needed = field.len() + if last { 0 } else { 2 };
+
+        if line_used && line_len + needed > opts.max_line_len {
+            result.push(LINE_ESCAPE);
+            result.push('\n');
+            result.push_str(CONTINUATION_INDENT);
+            line_len = CONTINUATION_INDENT.len();
+        }
+
+        result.push_str(field);
+        line_len += field.len();
+        line_used = true;
+
+        if !last {
+            result.push(DELIM);
+            line_len += 1;
+        }
+    }
+
+    result
+}
+
+/// Splits the data part of a record on commas, except for commas inside
+/// quoted strings. (Paths in PARTNO/ can have those.)
+pub fn split_fields(data: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in data.chars() {
+        if c == QUOTE {
+            quoted = !quoted;
+        }
+
+        if c == DELIM && !quoted {
+            result.push(current);
+            current = String::new();
+        }
+        else {
+            current.push(c);
+        }
+    }
+
+    result.push(current);
+    result
+}
+
+/// Reads a number from a record. Only finite numbers are allowed, since
+/// anything else would break the geometry.
+pub fn parse_number(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(n) if n.is_finite() => Ok(n),
+        _ => Err(format!("Bad number {:?}", s.trim())),
+    }
+}
+
+/// Removes any trailing comment from a physical line, then checks whether
+/// it gets continued on the next line.
+///
+/// Returns the content of the line (without the '$' or comment) and whether
+/// the record continues.
+pub fn strip_continuation(line: &str) -> (&str, bool) {
+    let line = strip_comment(line).trim_end();
+
+    match line.strip_suffix(LINE_ESCAPE) {
+        Some(content) => (content.trim_end(), true),
+        None => (line, false),
+    }
+}
+
+/// Removes a "$$" comment, as long as it isn't inside a quoted string.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+
+    for (i, c) in line.char_indices() {
+        if c == QUOTE {
+            quoted = !quoted;
+        }
+        else if !quoted && line[i..].starts_with(COMMENT_START) {
+            return &line[..i];
+        }
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(data: &str) -> Vec<String> {
+        data.split(',').map(String::from).collect()
+    }
+
+    #[test]
+    fn wrap() {
+        let opts = WriteOptions { max_line_len: 20, ..WriteOptions::default() };
+
+        assert_eq!(write_record("L1=LINE/", &fields("1,2,3,4"), &opts),
+                   "L1=LINE/1,2,3,4");
+        assert_eq!(write_record("L1=LINE/", &fields("1.25,2.25,3.25,4.25"), &opts),
+                   "L1=LINE/1.25,2.25,$\n              3.25,$\n              4.25");
+
+        for l in write_record("L1=LINE/", &fields("1.25,2.25,3.25,4.25"), &opts).lines() {
+            assert!(l.len() <= opts.max_line_len);
+        }
+    }
+
+    #[test]
+    fn continuation() {
+        assert_eq!(strip_continuation("LINE/1,2,$"), ("LINE/1,2,", true));
+        assert_eq!(strip_continuation("LINE/1,2,$  \t"), ("LINE/1,2,", true));
+        assert_eq!(strip_continuation("LINE/1,2,$ $$ more below"), ("LINE/1,2,", true));
+        assert_eq!(strip_continuation("LINE/1,2 $$ no more"), ("LINE/1,2", false));
+        assert_eq!(strip_continuation("PARTNO/'a $$ b',1"), ("PARTNO/'a $$ b',1", false));
+    }
+
+    #[test]
+    fn numbers() {
+        let plain = NumberFormat::default();
+        for v in &[0., 1., -2.5, 0.0261721465846, 1e-9, 123456789.125, 1e22] {
+            assert_eq!(plain.format(*v), v.to_string());
+        }
+        assert_eq!(plain.format(-0.), "0");
+
+        let exporter = NumberFormat {
+            sig_digits: Some(14),
+            trailing_dot: true,
+            sci_min_exp: -8,
+            sci_max_exp: 12,
+        };
+        assert_eq!(exporter.format(120.), "120.");
+        assert_eq!(exporter.format(0.), "0.");
+        assert_eq!(exporter.format(0.1 + 0.2), "0.3");
+        let noisy = f64::from_bits(1.5936069677454_f64.to_bits() + 3);
+        assert_eq!(exporter.format(noisy), "1.5936069677454");
+        assert_eq!(exporter.format(-0.00000000015), "-1.5E-10");
+        assert_eq!(exporter.format(3e13), "3.E13");
+        assert_eq!(exporter.format(99.999999999999999), "100.");
+    }
+
+    #[test]
+    fn labels() {
+        let opts = WriteOptions::default();
+        assert_eq!(format_label('L', 17, &opts), "L00017");
+        assert_eq!(format_label('C', 123456, &opts), "C123456");
+        assert_eq!(parse_label("C123456"), Some(('C', 123456)));
+        assert_eq!(record_label("L00017=LINE/1,1,2,2").as_deref(), Some("L00017"));
+        assert_eq!(record_label(" =LINE/1,1,2,2"), None);
+
+        let mut per_type = Labeler::new(LabelScheme::PerType);
+        assert_eq!(per_type.next('L', 1), 1);
+        assert_eq!(per_type.next('C', 1), 1);
+        assert_eq!(per_type.next('L', 2), 2);
+
+        let mut per_level = Labeler::new(LabelScheme::PerLevel);
+        assert_eq!(per_level.next('L', 1), 1);
+        assert_eq!(per_level.next('C', 1), 2);
+        assert_eq!(per_level.next('L', 2), 1);
+
+        assert_eq!(LabelScheme::detect(vec![(1, "L1"), (1, "C2"), (2, "L3")]),
+                   LabelScheme::Global);
+        assert_eq!(LabelScheme::detect(vec![(1, "L1"), (1, "C1"), (2, "L2")]),
+                   LabelScheme::PerType);
+        assert_eq!(LabelScheme::detect(vec![(1, "L1"), (1, "C2"), (2, "L1")]),
+                   LabelScheme::PerLevel);
+    }
+
+    #[test]
+    fn quoted_fields() {
+        assert_eq!(split_fields("'a,b',1"), vec!["'a,b'", "1"]);
+    }
+}
@@ -15,66 +15,110 @@
 
 pub mod line;
 pub mod arc;
+pub mod curve;
+
+use super::nfl::{self, WriteOptions, Labeler};
+use super::report::{PairReport, LineSplit};
+use super::color::Color;
 
 use line::Line;
 use arc::Arc;
-use std::collections::HashMap;
+use curve::Curve;
+use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
 use euclid::Point2D;
 use log::*;
 //use crate::assembly::line::Line;
 //use self::line;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Part {
 	pub level: u64,
-	pub data: HashMap<String, String>,
-	pub lines: Vec<Line>,
-	pub arcs: Vec<Arc>
+
+	/// Attributes such as COLOR and FONT, without the '/'
+	#[serde(rename = "attrs", default)]
+	pub data: BTreeMap<String, String>,
+
+	/// Everything the part is drawn with, in the order it was read
+	#[serde(default)]
+	pub curves: Vec<Curve>
 }
 
 impl Part {
+	/// Creates a part with nothing in it.
+	pub fn empty(level: u64) -> Part {
+		Part { level, data: BTreeMap::new(), curves: Vec::new() }
+	}
+
+	/// Adds a line from p1 to p2.
+	pub fn add_line(&mut self, p1: Point2D<f64, f64>, p2: Point2D<f64, f64>) -> &mut Part {
+		self.curves.push(Line::from_points(p1, p2).into());
+		self
+	}
+
+	/// Adds an arc going counterclockwise from go_angle to end_angle,
+	/// both in degrees.
+	pub fn add_arc(&mut self, center: Point2D<f64, f64>, radius: f64,
+	               go_angle: f64, end_angle: f64) -> &mut Part
+	{
+		self.curves.push(Arc::from_angles(center, radius, go_angle, end_angle).into());
+		self
+	}
+
+	/// Adds a whole circle.
+	pub fn add_circle(&mut self, center: Point2D<f64, f64>, radius: f64) -> &mut Part {
+		self.curves.push(Arc::circle(center, radius).into());
+		self
+	}
+
+	/// Gets the part's COLOR/, if it has one that's a known color.
+	pub fn color(&self) -> Option<Color> {
+		self.data.get("COLOR").and_then(|c| Color::parse(c))
+	}
+
 	/// Creates a new part from the set of raw data from the file.
 	/// This data does not need to be processed before hand, just each
 	/// line should be separate, as it was in the file.
-	pub fn new(level: u64, data: Vec<String>) -> Part {
-
-		let mut result = Part{
-			level,
-			data: HashMap::new(),
-			lines: Vec::new(),
-			arcs: Vec::new()
-		};
+	pub fn new(level: u64, data: Vec<String>) -> Result<Part, String> {
+
+		let mut result = Part::empty(level);
 		
         debug!("Processing part {}", result.level);
 
 		let line_tag = "LINE/";
 		let circle_tag = "CIRCLE/";
-		let line_escape = '$';
 		let data_separator = '/';
 		
 		// We'll use a while loop because we sometimes need to consume
 		// multiple lines in one loop. (Lines can be broken up with '$' chars.)
 		let mut i: usize = 0;
 		while i < data.len() {
-			let mut line = data[i].clone();
+			let (content, mut continues) = nfl::strip_continuation(&data[i]);
+			let mut line = String::from(content);
 			
-			while line.ends_with(line_escape) {
-				// Remove trailing '$'
-				line = String::from(line.trim_end_matches(line_escape));
+			while continues && i + 1 < data.len() {
+				i += 1;
+
+				let (content, c) = nfl::strip_continuation(&data[i]);
+				line.push_str(content.trim());
+				continues = c;
+			}
 
+			// Lines that were nothing but a comment
+			if line.trim().is_empty() {
 				i += 1;
-				line.push_str(data[i].trim());
+				continue;
 			}
 			
 			if line.contains(line_tag) {
-				result.lines.push(Line::from_nfl(line.as_str()));
+				result.curves.push(Line::from_nfl(line.as_str())?.into());
 			}
 			else if line.contains(circle_tag) {
-				result.arcs.push(Arc::new(line.as_str()));
+				result.curves.push(Arc::new(line.as_str())?.into());
 			}
 			else {
-				// Making hard assumption that this is formatted right
-				let split = line.find(data_separator).unwrap();
+				let split = line.find(data_separator)
+					.ok_or_else(|| format!("Bad record {:?}", line))?;
 				result.data.insert(
 					String::from(&line[..split]), // Part before data separator
 					String::from(&line[split+1..])// Part after
@@ -86,14 +130,36 @@ impl Part {
 
         debug!("Finished part {}", result.level);
 
-		result
+		Ok(result)
+	}
+
+	/// Moves everything from the other part into this one. Where both have
+	/// the same attribute, this part's is kept.
+	pub fn merge(&mut self, other: Part) {
+		for (key, value) in other.data {
+			self.data.entry(key).or_insert(value);
+		}
+
+		self.curves.extend(other.curves);
+	}
+
+	/// The part's lines, in order.
+	pub fn lines(&self) -> impl Iterator<Item = &Line> {
+		self.curves.iter().filter_map(Curve::as_line)
 	}
 
-    pub fn resolve_overlaps(a: &mut Part, b: &mut Part, max_dist: f64) {
+	/// The part's arcs and circles, in order.
+	pub fn arcs(&self) -> impl Iterator<Item = &Arc> {
+		self.curves.iter().filter_map(Curve::as_arc)
+	}
+
+    /// Splits the curves of both parts wherever they overlap, so that they
+    /// share endpoints. Returns what was done.
+    pub fn resolve_overlaps(a: &mut Part, b: &mut Part, max_dist: f64) -> PairReport {
         info!("Resolving parts {} and {}", a.level, b.level);
 
-        // So the problem is that we need to replace lines... but we
-        // cannot just add lines while we're looping through!
+        // So the problem is that we need to replace curves... but we
+        // cannot just add curves while we're looping through!
         // 
         // Instead, no splits will occur until we're done finding points
         // to split at. Once we do that, we'll go through this list of
@@ -111,79 +177,168 @@ impl Part {
         // 
         // Note that we CANNOT destroy the endpoint!
         
-        // These maps are arranged such that the key is the index to the line,
-        // while the points are all the places that those lines must be split.
+        // These maps are arranged such that the key is the index to the curve,
+        // while the points are all the places that those curves must be split.
         let mut a_splits: HashMap<usize, Vec<Point2D<f64, f64>>> = HashMap::new();
         let mut b_splits: HashMap<usize, Vec<Point2D<f64, f64>>> = HashMap::new();
+        let mut overlap_count = 0;
 
         let add_split = |m: &mut HashMap<usize, Vec<_>>, key, points: &(_,_)|
         {
-            if !m.contains_key(&key) {
-                m.insert(key, Vec::new());
-            }
-
-            m.get_mut(&key).unwrap().push(points.0);
-            m.get_mut(&key).unwrap().push(points.1);
+            let split_points = m.entry(key).or_insert_with(Vec::new);
+            split_points.push(points.0);
+            split_points.push(points.1);
         };
 
-        for i in 0..a.lines.len() {
-            for j in 0..b.lines.len() {
+        for i in 0..a.curves.len() {
+            for j in 0..b.curves.len() {
                 
-                if let Some(overlaps)
-                    = Line::find_overlaps(&a.lines[i], &b.lines[j], max_dist)
-                {
-                    trace!("Found overlap in lines:\na: {}\nb: {}\no: {:?}",
-                           a.lines[i], b.lines[j], overlaps);
+                for overlaps in Curve::find_overlaps(&a.curves[i], &b.curves[j], max_dist) {
+                    trace!("Found overlap in curves:\na: {}\nb: {}\no: {:?}",
+                           a.curves[i], b.curves[j], overlaps);
 
                     add_split(&mut a_splits, i, &overlaps);
                     add_split(&mut b_splits, j, &overlaps);
+                    overlap_count += 1;
                 }
                 
             }
         }
 
 
-        let perform_splits = |lines: &mut Vec<Line>, splits: HashMap<usize, Vec<Point2D<f64,f64>>>, name: &str| {
-            for (i, points) in splits.into_iter() {
+        let perform_splits = |curves: &mut Vec<Curve>, mut splits: HashMap<usize, Vec<Point2D<f64,f64>>>, level: u64| {
+            let mut broken = Vec::new();
 
-                let mut new_lines = lines[i].split(points, max_dist);
-                trace!("{}: Breaking {:?} into \n{:#?}", name, lines[i], new_lines);
+            // The pieces take the place of the curve they came from, so
+            // the part keeps its order and the report comes out the same
+            // every time.
+            for (i, curve) in std::mem::take(curves).into_iter().enumerate() {
+                let points = match splits.remove(&i) {
+                    Some(points) => points,
+                    None => {
+                        curves.push(curve);
+                        continue;
+                    }
+                };
 
-                lines.append(&mut new_lines);
+                let pieces = curve.split(points, max_dist);
+                trace!("{}: Breaking {:?} into \n{:#?}", level, curve, pieces);
 
-                // We want to do use swap_remove because it preserves
-                // the indicies for the rest of the existing lines,
-                // which is important because that's how we track lines.
-                lines.swap_remove(i);
+                if pieces.len() > 1 {
+                    broken.push(LineSplit::new(level, &curve, &pieces));
+                }
 
+                curves.extend(pieces);
             }
+
+            broken
         };
 
-        perform_splits(&mut a.lines, a_splits, "a");
-        perform_splits(&mut b.lines, b_splits, "b");
+        let mut broken = perform_splits(&mut a.curves, a_splits, a.level);
+        broken.extend(perform_splits(&mut b.curves, b_splits, b.level));
+
+        PairReport {
+            levels: [a.level, b.level],
+            overlaps: overlap_count,
+            lines: broken,
+        }
+    }
+
+    /// Checks whether resolving the overlaps between these two parts would
+    /// break up any of their curves.
+    pub fn needs_split(a: &Part, b: &Part, max_dist: f64) -> bool {
+        a.curves.iter().any(|ca| {
+            b.curves.iter().any(|cb| {
+                Curve::find_overlaps(ca, cb, max_dist).iter().any(|(p1, p2)| {
+                    [ca, cb].iter().any(|c| {
+                        c.contains(p1, max_dist) || c.contains(p2, max_dist)
+                    })
+                })
+            })
+        })
+    }
+
+    /// Gets the original labels of everything in this part, for things
+    /// that were read from a file.
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.curves.iter().filter_map(Curve::label)
     }
 
-	pub fn to_nfl(&self, id: &mut u64) -> String {
+	pub fn to_nfl(&self, labeler: &mut Labeler, opts: &WriteOptions) -> String {
 		let mut result = String::new();
 		
 		result += &format!("LEVEL/{}\n", self.level);
 		
 		for l in &self.data {
-			result += &format!("{}/{}\n",l.0, l.1);
-		}
-		
-		for l in &self.lines {
-			result += &l.to_nfl(*id);
+			result += &nfl::write_record(
+				&format!("{}/", l.0), &nfl::split_fields(l.1), opts
+			);
 			result.push('\n');
-			*id += 1;
 		}
-
-		for a in &self.arcs {
-			result += &a.to_nfl(*id);
+		
+		for c in &self.curves {
+			result += &c.to_nfl(labeler.next(c.prefix(), self.level), opts);
 			result.push('\n');
-			*id += 1;
 		}
 		
 		result
 	}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_DIST: f64 = 0.000001;
+
+    #[test]
+    fn line_ends_on_arc() {
+        let mut a = Part::empty(1);
+        a.add_arc(Point2D::new(0., 0.), 1., 0., 180.);
+        let mut b = Part::empty(2);
+        b.add_line(Point2D::new(0., 1.), Point2D::new(0., 3.));
+
+        assert!(Part::needs_split(&a, &b, MAX_DIST));
+        let report = Part::resolve_overlaps(&mut a, &mut b, MAX_DIST);
+        assert_eq!(report.lines.len(), 1);
+
+        let angles: Vec<_> = a.arcs().map(|a| (a.go_angle, a.end_angle)).collect();
+        assert_eq!(angles, vec![(0., 90.), (90., 180.)]);
+        assert_eq!(b.curves.len(), 1);
+        assert!(!Part::needs_split(&a, &b, MAX_DIST));
+    }
+
+    #[test]
+    fn arc_ends_on_line() {
+        let mut a = Part::empty(1);
+        a.add_line(Point2D::new(-3., 0.), Point2D::new(3., 0.));
+        let mut b = Part::empty(2);
+        b.add_arc(Point2D::new(0., 1.), 1., 180., 270.);
+
+        assert!(Part::needs_split(&a, &b, MAX_DIST));
+        let report = Part::resolve_overlaps(&mut a, &mut b, MAX_DIST);
+        assert_eq!(report.lines.len(), 1);
+
+        let ends: Vec<_> = a.lines().map(|l| (l.p1(), l.p2())).collect();
+        assert_eq!(ends, vec![(Point2D::new(-3., 0.), Point2D::new(0., 0.)),
+                              (Point2D::new(0., 0.), Point2D::new(3., 0.))]);
+        assert_eq!(b.curves.len(), 1);
+        assert!(!Part::needs_split(&a, &b, MAX_DIST));
+    }
+
+    #[test]
+    fn arcs_overlap() {
+        // A circle, and a half circle from the top round to the bottom
+        let mut a = Part::empty(1);
+        a.add_circle(Point2D::new(0., 0.), 1.);
+        let mut b = Part::empty(2);
+        b.add_arc(Point2D::new(0., 0.), 1., 90., 270.);
+
+        Part::resolve_overlaps(&mut a, &mut b, MAX_DIST);
+
+        let angles: Vec<_> = a.arcs().map(|a| (a.go_angle, a.end_angle)).collect();
+        assert_eq!(angles, vec![(0., 90.), (90., 270.), (270., 360.)]);
+        assert_eq!(b.curves.len(), 1);
+        assert!(!Part::needs_split(&a, &b, MAX_DIST));
+    }
+}
@@ -14,12 +14,26 @@
 // along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
 
 use regex::Regex;
-use euclid::Point2D;
+use std::fmt;
+use euclid::{Box2D, Point2D, Vector2D};
+use serde::{Deserialize, Serialize};
+use crate::assembly::nfl::{self, WriteOptions};
 
 const DEFAULT_GO_ANGLE: f64 = 0.0;
 const DEFAULT_END_ANGLE: f64 = 360.0;
 
-#[derive(Debug)]
+fn default_go_angle() -> f64 { DEFAULT_GO_ANGLE }
+fn default_end_angle() -> f64 { DEFAULT_END_ANGLE }
+
+/// Brings an angle in degrees into [0, 360).
+pub fn normalize_angle(angle: f64) -> f64 {
+	let angle = angle.rem_euclid(360.0);
+
+	// rem_euclid can round up to exactly 360 for tiny negative angles
+	if angle >= 360.0 { 0.0 } else { angle }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Arc {
 
 	// "C00023=CIRCLE/CENTER,0.8045921410692,1.9847924952923,RADIUS,0.4838867345605,GOANG,90.140087201328,ENDANG,256.45889291795"
@@ -29,73 +43,344 @@ pub struct Arc {
     */
     pub center: Point2D<f64, f64>,
 	pub radius: f64,
+	#[serde(default = "default_go_angle")]
 	pub go_angle: f64,
-	pub end_angle: f64
+	#[serde(default = "default_end_angle")]
+	pub end_angle: f64,
+
+	// The label this had in the file it was read from, if any
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub label: Option<String>
 }
 
 impl Arc {
 
-	pub fn new(data: &str) -> Arc {
-		let mut result = Arc{
-			center: Point2D::new(0.0, 0.0), radius: 0.0,
-			go_angle: DEFAULT_GO_ANGLE, end_angle: DEFAULT_END_ANGLE
-		};
+	pub fn from_angles(center: Point2D<f64, f64>, radius: f64, go_angle: f64, end_angle: f64) -> Arc {
+		Arc{ center, radius, go_angle, end_angle, label: None }
+	}
+
+	pub fn circle(center: Point2D<f64, f64>, radius: f64) -> Arc {
+		Arc::from_angles(center, radius, DEFAULT_GO_ANGLE, DEFAULT_END_ANGLE)
+	}
+
+	pub fn new(data: &str) -> Result<Arc, String> {
+		let mut result = Arc::circle(Point2D::new(0.0, 0.0), 0.0);
+		result.label = nfl::record_label(data);
 
 		let trimmer = Regex::new(r"^.*/").unwrap();
-		let data = String::from(trimmer.replace_all(data, ""));
-		let mut split = data.split(',');
+		let fields = String::from(trimmer.replace_all(data, ""));
+		let mut split = fields.split(',');
+		let number = |s: Option<&str>| match s {
+			Some(s) => nfl::parse_number(s).map_err(|e| format!("{} in {:?}", e, data)),
+			None => Err(format!("Missing number in {:?}", data)),
+		};
 		
-		let mut next = split.next();
-		while next != None {
-			let s = next.unwrap();
+		while let Some(s) = split.next() {
 			
 			if s == "CENTER" {
-				result.center.x = split.next().unwrap().parse::<f64>().unwrap();
-				result.center.y = split.next().unwrap().parse::<f64>().unwrap();
+				result.center.x = number(split.next())?;
+				result.center.y = number(split.next())?;
 			}
 			else if s == "RADIUS" {
-				result.radius = split.next().unwrap().parse::<f64>().unwrap();
+				result.radius = number(split.next())?;
 			}
 			else if s == "GOANG" {
-				result.go_angle = split.next().unwrap().parse::<f64>().unwrap();
+				result.go_angle = number(split.next())?;
 			}
 			else if s == "ENDANG" {
-				result.end_angle = split.next().unwrap().parse::<f64>().unwrap();
+				result.end_angle = number(split.next())?;
 			}
-			
-			next = split.next();
 		}
 		
-		result
+		Ok(result)
 	}
 
-	pub fn to_nfl(&self, id: u64) -> String {
+	/// How far the arc goes around, in degrees, going counterclockwise
+	/// from GOANG to ENDANG. This is always more than 0 and at most 360,
+	/// so an arc with GOANG past ENDANG wraps through 0, and one where they
+	/// are the same (or 360 apart) is a full circle.
+	pub fn sweep(&self) -> f64 {
+		let sweep = normalize_angle(self.end_angle - self.go_angle);
 
-        let delim = ",";
-        let line_break = "$\n";
-        let line_break_post = "              ";
-        let max_line_len = 79;
+		if sweep == 0.0 { 360.0 } else { sweep }
+	}
 
-        // All lines have this, and they'll probably be on the same line.
-		let mut result = format!("C{:0>5}=CIRCLE/CENTER,{},{},RADIUS,{}", id, self.center.x, self.center.y, self.radius);
+	/// Checks if this is a whole circle rather than part of one.
+	pub fn is_circle(&self) -> bool {
+		self.sweep() == 360.0
+	}
 
-        let mut parts = Vec::new();
-		if self.go_angle  != DEFAULT_GO_ANGLE  { parts.push(format!("GOANG,{}", self.go_angle)); }
-		if self.end_angle != DEFAULT_END_ANGLE { parts.push(format!("ENDANG,{}", self.end_angle)); }
+	/// The point at GOANG.
+	pub fn start(&self) -> Point2D<f64, f64> {
+		self.point_at_angle(self.go_angle)
+	}
+
+	/// The point at ENDANG.
+	pub fn end(&self) -> Point2D<f64, f64> {
+		self.point_at_angle(self.go_angle + self.sweep())
+	}
+
+	/// The angle, in degrees from the positive x axis, at which the point
+	/// lies as seen from the center. Always in [0, 360).
+	pub fn angle_of(&self, point: &Point2D<f64, f64>) -> f64 {
+		let v = *point - self.center;
+		normalize_angle(v.y.atan2(v.x).to_degrees())
+	}
+
+	/// How far past GOANG, counterclockwise, the given angle is.
+	/// Always in [0, 360).
+	pub fn offset_of(&self, angle: f64) -> f64 {
+		normalize_angle(angle - self.go_angle)
+	}
+
+	/// Gets the point on the circle at the given angle, in degrees. Points
+	/// on the axes are exact, so they meet lines drawn to them; cos(90°)
+	/// would otherwise come out as 6e-17.
+	pub fn point_at_angle(&self, angle: f64) -> Point2D<f64, f64> {
+		let direction = match normalize_angle(angle) {
+			0.0 => Vector2D::new(1.0, 0.0),
+			90.0 => Vector2D::new(0.0, 1.0),
+			180.0 => Vector2D::new(-1.0, 0.0),
+			270.0 => Vector2D::new(0.0, -1.0),
+			a => Vector2D::new(a.to_radians().cos(), a.to_radians().sin()),
+		};
+
+		self.center + direction * self.radius
+	}
+
+	/// Checks if the point is on the arc, including the arc's endpoints.
+	pub fn overlaps(&self, point: &Point2D<f64, f64>, max_dist: f64) -> bool {
+		if ((*point - self.center).length() - self.radius).abs() > max_dist {
+			return false;
+		}
+
+		self.offset_of(self.angle_of(point)) <= self.sweep()
+			|| (*point - self.start()).length() <= max_dist
+			|| (*point - self.end()).length() <= max_dist
+	}
+
+	/// Checks if the point is on the arc, excluding the arc's endpoints.
+	/// A circle starts and ends at GOANG, which is where its one vertex is,
+	/// so that point doesn't count either.
+	pub fn contains(&self, point: &Point2D<f64, f64>, max_dist: f64) -> bool {
+		self.overlaps(point, max_dist)
+			&& (*point - self.start()).length() > max_dist
+			&& (*point - self.end()).length() > max_dist
+	}
+
+	/// The angle, in degrees, that a stretch of max_dist along the circle
+	/// takes up.
+	fn angle_within(&self, max_dist: f64) -> f64 {
+		if self.radius > 0.0 { (max_dist / self.radius).to_degrees() } else { 0.0 }
+	}
+
+	/// If a and b lie on the same circle and run along each other, gets the
+	/// start and end points of each stretch where they do, in order around
+	/// a. Arcs that only meet at their ends don't overlap.
+	pub fn find_overlaps(a: &Arc, b: &Arc, max_dist: f64)
+		-> Vec<(Point2D<f64, f64>, Point2D<f64, f64>)>
+	{
+		if (a.center - b.center).length() > max_dist || (a.radius - b.radius).abs() > max_dist {
+			return Vec::new();
+		}
+
+		// How far around a each arc starts and ends. Whatever is between
+		// two of these is either on both arcs or only on a.
+		let sweep = a.sweep();
+		let mut offsets = vec![0.0, sweep];
+		for p in &[b.start(), b.end()] {
+			let offset = a.offset_of(a.angle_of(p));
+			if offset < sweep {
+				offsets.push(offset);
+			}
+		}
+		offsets.sort_by(f64::total_cmp);
+
+		let min_angle = a.angle_within(max_dist);
+		offsets.windows(2)
+			.filter(|w| w[1] - w[0] > min_angle)
+			.filter(|w| b.overlaps(&a.point_at_angle(a.go_angle + (w[0] + w[1]) / 2.0), max_dist))
+			.map(|w| (a.point_at_angle(a.go_angle + w[0]), a.point_at_angle(a.go_angle + w[1])))
+			.collect()
+	}
 
-        if parts.len() > 0 {
-            let angle_part = parts.join(delim);
+	/// Breaks the arc up at the given points, which should be on it. The
+	/// pieces go around the same way the arc does. Points within max_dist
+	/// of the arc's ends or of each other are only used once, so a circle
+	/// split at one point becomes two arcs that meet at its GOANG.
+	pub fn split(&self, points: Vec<Point2D<f64, f64>>, max_dist: f64) -> Vec<Arc> {
+		let sweep = self.sweep();
+		let min_angle = self.angle_within(max_dist);
 
-            result.push_str(delim);
-            if result.len() + angle_part.len() + line_break.len() + delim.len() >= max_line_len {
-                result.push_str(line_break);
-                result.push_str(line_break_post);
-            }
+		let mut offsets: Vec<f64> = points.iter()
+			.map(|p| self.offset_of(self.angle_of(p)))
+			.filter(|&o| o > min_angle && o < sweep - min_angle)
+			.collect();
+		offsets.sort_by(f64::total_cmp);
 
-            result.push_str(&angle_part);
+		let mut cuts: Vec<f64> = Vec::new();
+		for o in offsets {
+			if cuts.last().is_none_or(|&last| o - last > min_angle) {
+				cuts.push(o);
+			}
+		}
+
+		if cuts.is_empty() {
+			return vec![self.clone()];
+		}
+
+		// The first and last pieces keep the arc's own angles as they were
+		let mut angles = vec![self.go_angle];
+		angles.extend(cuts.iter().map(|o| normalize_angle(self.go_angle + o)));
+		angles.push(self.end_angle);
+
+		angles.windows(2)
+			.map(|w| Arc::from_angles(self.center, self.radius, w[0], w[1]))
+			.collect()
+	}
+
+	/// How long the arc is, measured along the circle.
+	pub fn length(&self) -> f64 {
+		self.radius * self.sweep().to_radians()
+	}
+
+	/// The smallest box that holds the arc. Besides the two ends, this takes
+	/// in every point where the arc crosses the left, right, top or bottom
+	/// of its circle.
+	pub fn bounding_box(&self) -> Box2D<f64, f64> {
+		let sweep = self.sweep();
+		let mut points = vec![self.start(), self.end()];
+
+		let mut angle = (self.go_angle / 90.0).ceil() * 90.0;
+		while angle < self.go_angle + sweep {
+			points.push(self.point_at_angle(angle));
+			angle += 90.0;
+		}
+
+		Box2D::from_points(&points)
+	}
+
+	pub fn to_nfl(&self, id: u64, opts: &WriteOptions) -> String {
+
+        // All arcs have these, and they'll probably be on the same line.
+        let num = |v| opts.number.format(v);
+        let mut fields = vec![
+            format!("CENTER,{},{}", num(self.center.x), num(self.center.y)),
+            format!("RADIUS,{}", num(self.radius)),
+        ];
+
+        // The original exporter keeps the angles together, so we do too.
+        let mut parts = Vec::new();
+		if self.go_angle  != DEFAULT_GO_ANGLE  { parts.push(format!("GOANG,{}", num(self.go_angle))); }
+		if self.end_angle != DEFAULT_END_ANGLE { parts.push(format!("ENDANG,{}", num(self.end_angle))); }
+
+        if !parts.is_empty() {
+            fields.push(parts.join(","));
         }
 		
-		result
+		nfl::write_record(&format!("{}=CIRCLE/", nfl::format_label('C', id, opts)), &fields, opts)
 	}
 }
 
+impl fmt::Display for Arc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Arc(({}, {}), r={}, {}..{})", self.center.x, self.center.y,
+               self.radius, self.go_angle, self.end_angle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Point2D<f64, f64>, b: Point2D<f64, f64>) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn wrap_around() {
+        let a = Arc::new("C1=CIRCLE/CENTER,0,0,RADIUS,2,GOANG,270,ENDANG,90").unwrap();
+
+        assert_eq!(a.sweep(), 180.);
+        assert!(close(a.start(), Point2D::new(0., -2.)));
+        assert!(close(a.end(), Point2D::new(0., 2.)));
+        assert!((a.length() - 2. * std::f64::consts::PI).abs() < 1e-9);
+
+        let b = a.bounding_box();
+        assert!(close(b.min, Point2D::new(0., -2.)));
+        assert!(close(b.max, Point2D::new(2., 2.)));
+    }
+
+    #[test]
+    fn angles() {
+        assert_eq!(normalize_angle(-90.), 270.);
+        assert_eq!(normalize_angle(720.), 0.);
+        assert_eq!(normalize_angle(-1e-20), 0.);
+
+        let circle = Arc::new("C1=CIRCLE/CENTER,1,1,RADIUS,1").unwrap();
+        assert!(circle.is_circle());
+        assert_eq!(circle.angle_of(&Point2D::new(1., 0.)), 270.);
+        assert_eq!(Arc::new("C1=CIRCLE/CENTER,0,0,RADIUS,1,GOANG,-10,ENDANG,350").unwrap().sweep(), 360.);
+    }
+
+    #[test]
+    fn contains() {
+        let a = Arc::new("C1=CIRCLE/CENTER,0,0,RADIUS,1,GOANG,0,ENDANG,90").unwrap();
+        let max_dist = 0.00001;
+
+        assert!(a.overlaps(&Point2D::new(1., 0.), max_dist));
+        assert!(!a.contains(&Point2D::new(1., 0.), max_dist));
+        assert!(a.contains(&a.point_at_angle(45.), max_dist));
+        assert!(!a.overlaps(&a.point_at_angle(135.), max_dist));
+        assert!(!a.overlaps(&Point2D::new(0.5, 0.5), max_dist));
+
+        // Just inside the circle, just past the end
+        assert!(a.overlaps(&Point2D::new(0.999999, -0.000001), max_dist));
+
+        // A circle's vertex is at GOANG
+        let circle = Arc::new("C1=CIRCLE/CENTER,0,0,RADIUS,1").unwrap();
+        assert!(circle.contains(&Point2D::new(0., 1.), max_dist));
+        assert!(!circle.contains(&Point2D::new(1., 0.), max_dist));
+    }
+
+    #[test]
+    fn find_overlaps() {
+        let max_dist = 0.00001;
+        let a = Arc::from_angles(Point2D::new(0., 0.), 1., 0., 180.);
+
+        // Overlapping from 90 to 180
+        let o = Arc::find_overlaps(&a, &Arc::from_angles(Point2D::new(0., 0.), 1., 90., 270.), max_dist);
+        assert_eq!(o.len(), 1);
+        assert!(close(o[0].0, Point2D::new(0., 1.)));
+        assert!(close(o[0].1, Point2D::new(-1., 0.)));
+
+        // Only meeting at an end, or on a different circle
+        assert!(Arc::find_overlaps(&a, &Arc::from_angles(Point2D::new(0., 0.), 1., 180., 270.), max_dist).is_empty());
+        assert!(Arc::find_overlaps(&a, &Arc::from_angles(Point2D::new(0., 0.), 2., 0., 180.), max_dist).is_empty());
+
+        // Wrapping through 0, over both ends of a 300 degree arc
+        let b = Arc::from_angles(Point2D::new(0., 0.), 1., 0., 300.);
+        let o = Arc::find_overlaps(&b, &Arc::from_angles(Point2D::new(0., 0.), 1., 270., 30.), max_dist);
+        assert_eq!(o.len(), 2);
+        assert!(close(o[0].0, Point2D::new(1., 0.)));
+        assert!(close(o[1].1, b.end()));
+    }
+
+    #[test]
+    fn split() {
+        let max_dist = 0.00001;
+        let a = Arc::from_angles(Point2D::new(0., 0.), 1., 270., 90.);
+
+        let pieces = a.split(vec![Point2D::new(1., 0.), Point2D::new(0., -1.), a.point_at_angle(45.)], max_dist);
+        let angles: Vec<_> = pieces.iter().map(|p| (p.go_angle, p.end_angle)).collect();
+        assert_eq!(angles, vec![(270., 0.), (0., 45.), (45., 90.)]);
+
+        let circle = Arc::circle(Point2D::new(0., 0.), 1.);
+        assert_eq!(circle.split(vec![Point2D::new(1., 0.)], max_dist).len(), 1);
+
+        let pieces = circle.split(vec![Point2D::new(-1., 0.)], max_dist);
+        assert_eq!(pieces.len(), 2);
+        assert_eq!((pieces[1].go_angle, pieces[1].end_angle), (180., 360.));
+        assert!(!pieces[0].is_circle());
+    }
+}
@@ -0,0 +1,205 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// Anything a part can be drawn with. Code that doesn't care whether it's
+// looking at a line or an arc should go through this.
+
+use std::fmt;
+use euclid::{Box2D, Point2D};
+use serde::{Deserialize, Serialize};
+use crate::assembly::nfl::WriteOptions;
+use super::line::Line;
+use super::arc::Arc;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Curve {
+    Line(Line),
+    Arc(Arc),
+}
+
+impl Curve {
+    /// The letter that starts this kind of entity's label in an NFL file.
+    pub fn prefix(&self) -> char {
+        match self {
+            Curve::Line(_) => 'L',
+            Curve::Arc(_) => 'C',
+        }
+    }
+
+    /// The label this had in its source file, if any.
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            Curve::Line(l) => l.label(),
+            Curve::Arc(a) => a.label.as_deref(),
+        }
+    }
+
+    pub fn as_line(&self) -> Option<&Line> {
+        match self {
+            Curve::Line(l) => Some(l),
+            Curve::Arc(_) => None,
+        }
+    }
+
+    pub fn as_arc(&self) -> Option<&Arc> {
+        match self {
+            Curve::Line(_) => None,
+            Curve::Arc(a) => Some(a),
+        }
+    }
+
+    /// Where the curve starts. Arcs start at GOANG.
+    pub fn start(&self) -> Point2D<f64, f64> {
+        self.point_at(0.)
+    }
+
+    /// Where the curve ends. Arcs end at ENDANG.
+    pub fn end(&self) -> Point2D<f64, f64> {
+        self.point_at(1.)
+    }
+
+    pub fn length(&self) -> f64 {
+        match self {
+            Curve::Line(l) => l.to_vector().length(),
+            Curve::Arc(a) => a.length(),
+        }
+    }
+
+    /// The smallest box that holds the whole curve.
+    pub fn bounding_box(&self) -> Box2D<f64, f64> {
+        match self {
+            Curve::Line(l) => Box2D::from_points([l.p1(), l.p2()]),
+            Curve::Arc(a) => a.bounding_box(),
+        }
+    }
+
+    /// Gets the point a fraction t of the way along the curve, where 0 is
+    /// the start and 1 is the end.
+    pub fn point_at(&self, t: f64) -> Point2D<f64, f64> {
+        match self {
+            Curve::Line(l) => l.p1().lerp(l.p2(), t),
+            Curve::Arc(a) => a.point_at_angle(a.go_angle + a.sweep() * t),
+        }
+    }
+
+    /// Checks if the point is on the curve, but not at either end.
+    pub fn contains(&self, point: &Point2D<f64, f64>, max_dist: f64) -> bool {
+        match self {
+            Curve::Line(l) => l.contains(point, max_dist),
+            Curve::Arc(a) => a.contains(point, max_dist),
+        }
+    }
+
+    /// If a and b run along each other, gets the start and end points of
+    /// each stretch where they do. A line can't run along an arc, but where
+    /// either ends part way along the other there's a stretch that starts
+    /// and ends there, since the other has to be split at that point.
+    pub fn find_overlaps(a: &Curve, b: &Curve, max_dist: f64)
+        -> Vec<(Point2D<f64, f64>, Point2D<f64, f64>)>
+    {
+        match (a, b) {
+            (Curve::Line(a), Curve::Line(b)) => Line::find_overlaps(a, b, max_dist).into_iter().collect(),
+            (Curve::Arc(a), Curve::Arc(b)) => Arc::find_overlaps(a, b, max_dist),
+            (Curve::Line(l), Curve::Arc(a)) | (Curve::Arc(a), Curve::Line(l)) => {
+                let line_ends = vec![l.p1(), l.p2()].into_iter().filter(|p| a.contains(p, max_dist));
+                let arc_ends = vec![a.start(), a.end()].into_iter().filter(|p| l.contains(p, max_dist));
+
+                line_ends.chain(arc_ends).map(|p| (p, p)).collect()
+            },
+        }
+    }
+
+    /// Breaks the curve up at the given points. See `Line::split` and
+    /// `Arc::split`.
+    pub fn split(&self, points: Vec<Point2D<f64, f64>>, max_dist: f64) -> Vec<Curve> {
+        match self {
+            Curve::Line(l) => l.split(points, max_dist).into_iter().map(Curve::Line).collect(),
+            Curve::Arc(a) => a.split(points, max_dist).into_iter().map(Curve::Arc).collect(),
+        }
+    }
+
+    pub fn to_nfl(&self, id: u64, opts: &WriteOptions) -> String {
+        match self {
+            Curve::Line(l) => l.to_nfl(id, opts),
+            Curve::Arc(a) => a.to_nfl(id, opts),
+        }
+    }
+}
+
+impl From<Line> for Curve {
+    fn from(l: Line) -> Curve {
+        Curve::Line(l)
+    }
+}
+
+impl From<Arc> for Curve {
+    fn from(a: Arc) -> Curve {
+        Curve::Arc(a)
+    }
+}
+
+impl fmt::Display for Curve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Curve::Line(l) => l.fmt(f),
+            Curve::Arc(a) => a.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Point2D<f64, f64>, b: Point2D<f64, f64>) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn line() {
+        let c = Curve::from(Line::new(0., 0., 2., 0.));
+
+        assert_eq!(c.length(), 2.);
+        assert!(close(c.point_at(0.25), Point2D::new(0.5, 0.)));
+        assert_eq!(c.bounding_box(), Box2D::new(Point2D::new(0., 0.), Point2D::new(2., 0.)));
+        assert_eq!(c.split(vec![Point2D::new(1., 0.)], 0.000001).len(), 2);
+    }
+
+    #[test]
+    fn arc() {
+        let c = Curve::from(Arc::new("C1=CIRCLE/CENTER,0,0,RADIUS,1,GOANG,0,ENDANG,180").unwrap());
+
+        assert!(close(c.start(), Point2D::new(1., 0.)));
+        assert!(close(c.end(), Point2D::new(-1., 0.)));
+        assert!(close(c.point_at(0.5), Point2D::new(0., 1.)));
+        assert!((c.length() - std::f64::consts::PI).abs() < 1e-9);
+
+        let b = c.bounding_box();
+        assert!(close(b.min, Point2D::new(-1., 0.)));
+        assert!(close(b.max, Point2D::new(1., 1.)));
+    }
+
+    #[test]
+    fn json() {
+        let line = Curve::from(Line::from_nfl("L00017=LINE/1,2,3,4").unwrap());
+        assert_eq!(serde_json::to_string(&line).unwrap(),
+                   r#"{"type":"line","p1":[1.0,2.0],"p2":[3.0,4.0],"label":"L00017"}"#);
+
+        let arc: Curve = serde_json::from_str(r#"{"type":"arc","center":[1,1],"radius":2}"#).unwrap();
+        assert!(arc.as_arc().unwrap().is_circle());
+        assert_eq!(arc.label(), None);
+    }
+}
@@ -15,42 +15,71 @@
 
 use regex::Regex;
 use std::fmt;
+use crate::assembly::nfl::{self, WriteOptions};
 use std::ops::{Index, IndexMut};
 //use std::cmp::Ordering;
 //use log::*;
 use euclid::{Point2D, Vector2D};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Line {
 
 	// "Lnnnnn=LINE/x1,y1,x2,y2",
     p1: Point2D<f64, f64>,
-    p2: Point2D<f64, f64>
+    p2: Point2D<f64, f64>,
+
+    // The label this had in the file it was read from, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>
 }
 
 impl Line {
 	
     pub fn new(x1: f64, y1: f64, x2: f64, y2: f64) -> Line {
-        Line{ p1: Point2D::new(x1, y1), p2: Point2D::new(x2, y2) }
+        Line::from_points(Point2D::new(x1, y1), Point2D::new(x2, y2))
     }
 
     pub fn from_points(p1: Point2D<f64, f64>, p2: Point2D<f64, f64>) -> Line {
-        Line{ p1, p2 }
+        Line{ p1, p2, label: None }
     }
 
-	pub fn from_nfl(data: &str) -> Line {
+	pub fn from_nfl(data: &str) -> Result<Line, String> {
 		
+		let original = data;
 		let trimmer = Regex::new(r"^.*/").unwrap();
 		let data = String::from(trimmer.replace_all(data, ""));
-		let split = data.split(',');
-		let mut converted = split.map(|x| x.parse::<f64>().unwrap());
+		let converted = data.split(',')
+			.map(nfl::parse_number)
+			.collect::<Result<Vec<f64>, String>>()
+			.map_err(|e| format!("{} in {:?}", e, original))?;
+
+		if converted.len() != 4 {
+			return Err(format!("Expected 4 numbers in {:?}", original));
+		}
 		
-        Line::new(
-            converted.next().unwrap(), converted.next().unwrap(),
-            converted.next().unwrap(), converted.next().unwrap()
-        )
+        let mut result = Line::new(converted[0], converted[1], converted[2], converted[3]);
+        result.label = nfl::record_label(original);
+
+        Ok(result)
 	}
 
+    /// The point the line starts at.
+    pub fn p1(&self) -> Point2D<f64, f64> {
+        self.p1
+    }
+
+    /// The point the line ends at.
+    pub fn p2(&self) -> Point2D<f64, f64> {
+        self.p2
+    }
+
+    /// The label this line had in its source file (i.e. "L00017").
+    /// Lines that came from splitting don't have one.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
     /// If a and b overlap, gets the start and end points of the
     /// overlapping segment. If they do not overlap, None is returned.
     ///
@@ -62,15 +91,15 @@ impl Line {
 
         let mut append_if_contained = |l: &Line, p: &Point2D<f64,f64>| {
             if l.overlaps(p, max_dist) {
-                overlaps.push(p.clone());
+                overlaps.push(*p);
             }
         };
 
         // We'll figure out which points are contained in the lines.
-        append_if_contained(&a, &b.p1);
-        append_if_contained(&a, &b.p2);
-        append_if_contained(&b, &a.p1);
-        append_if_contained(&b, &a.p2);
+        append_if_contained(a, &b.p1);
+        append_if_contained(a, &b.p2);
+        append_if_contained(b, &a.p1);
+        append_if_contained(b, &a.p2);
 
         // If we see three overlaps, then the lines share at least one point.
         // If we see four, the lines perfectly match and nothing more must be done.
@@ -110,7 +139,7 @@ impl Line {
         // D = ||L x p0p1||/||L|| = ||a1a2 x a1b1||/||a1a2||
         let d = v.cross(self.p1 - *point) / l;
         if d.abs() > max_dist {
-            return false;
+            false
         }
         else {
             // Alright, we've established that this point is on the (infinite)
@@ -127,12 +156,11 @@ impl Line {
             
             let p1_len = (*point - self.p1).square_length();
             let p2_len = (*point - self.p2).square_length();
-            return (p1_len < l*l && p2_len < l*l) || p1_len == 0. || p2_len == 0.;
+            (p1_len < l*l && p2_len < l*l) || p1_len == 0. || p2_len == 0.
         }
     }
 
     /// Checks if a line contains the given point, excluding the line's endpoints.
-    #[allow(dead_code)] // Rust won't stop complaining about this
     pub fn contains(&self, point: &Point2D<f64, f64>, max_dist: f64) -> bool {
 
         let dist_sqr = max_dist * max_dist;
@@ -150,36 +178,47 @@ impl Line {
     /// the assumption is that we are trying to break a line up the line
     /// based on the given points, which should be reasonably close to the line.
     /// Thus, points that are too close to the current line's endpoints are ignored.
-    pub fn split(&self, mut points: Vec<Point2D<f64,f64>>, _max_dist: f64) -> Vec<Line> {
-        points.push(self.p1);
-        points.push(self.p2);
+    pub fn split(&self, points: Vec<Point2D<f64,f64>>, max_dist: f64) -> Vec<Line> {
+        // The endpoints go first, as the earlier of two close points is
+        // the one kept.
+        let mut points: Vec<(usize, Point2D<f64,f64>)> = vec![self.p1, self.p2].into_iter()
+            .chain(points)
+            .enumerate()
+            .collect();
 
         let v = self.to_vector();
         if v.x.abs() > v.y.abs() {
             // Line is horizontal; we should see a bigger difference in
             // x values, so those should be less likely to be equal.
-            points.sort_by(|a, b| { a.x.partial_cmp(&b.x).unwrap() })
+            points.sort_by(|a, b| { a.1.x.partial_cmp(&b.1.x).unwrap() })
         }
         else {
-            points.sort_by(|a, b| { a.y.partial_cmp(&b.y).unwrap() })
+            points.sort_by(|a, b| { a.1.y.partial_cmp(&b.1.y).unwrap() })
         }
 
 
-        // Delete duplicates. Yes, we are looking for perfect matches.
+        // Delete duplicates: points within max_dist of each other, keeping
+        // whichever was given first. The line's own endpoints always win,
+        // so a cut right next to one doesn't leave a sliver behind.
         let mut i = 1;
         while i < points.len() {
-            if points[i-1] == points[i] {
-                points.remove(i);
+            let (a, b) = (points[i-1], points[i]);
+
+            if (b.1 - a.1).length() > max_dist || (a.0 < 2 && b.0 < 2 && a.1 != b.1) {
+                i+=1;
+            }
+            else if b.0 < a.0 {
+                points.remove(i-1);
             }
             else {
-                i+=1;
+                points.remove(i);
             }
         }
 
         i = 1;
         let mut result: Vec<Line> = Vec::new();
         while i < points.len() {
-            result.push(Line::from_points(points[i-1], points[i]));
+            result.push(Line::from_points(points[i-1].1, points[i].1));
             i+=1;
         }
 
@@ -201,13 +240,15 @@ impl Line {
         }
     }
 
-	pub fn to_nfl(&self, id: u64) -> String {
+	pub fn to_nfl(&self, id: u64, opts: &WriteOptions) -> String {
         let p1 = &self.p1;
         let p2 = &self.p2;
 
-		format!("L{:0>5}=LINE/{},{},{},{}",
-			id, p1.x, p1.y, p2.x, p2.y
-		)
+        let fields: Vec<String> = [p1.x, p1.y, p2.x, p2.y].iter()
+            .map(|v| opts.number.format(*v))
+            .collect();
+
+		nfl::write_record(&format!("{}=LINE/", nfl::format_label('L', id, opts)), &fields, opts)
 	}
 	
 }
@@ -248,12 +289,14 @@ mod tests {
 
     #[test]
     fn nfl() {
-		let line = Line::from_nfl(String::from("L00017=LINE/1,1,2,2").as_str());
+		let line = Line::from_nfl(String::from("L00017=LINE/1,1,2,2").as_str()).unwrap();
 
         assert_eq!(line.p1, Point2D::new(1.,1.));
         assert_eq!(line.p2, Point2D::new(2.,2.));
+        assert_eq!(line.label(), Some("L00017"));
 
-        assert_eq!(line.to_nfl(101), String::from("L00101=LINE/1,1,2,2"));
+        assert_eq!(line.to_nfl(101, &WriteOptions::default()),
+                   String::from("L00101=LINE/1,1,2,2"));
     }
 
     #[test]
@@ -289,4 +332,16 @@ mod tests {
 
         assert!(Line::find_overlaps(&l1, &l2, 0.000001).is_some());
     }
+
+    #[test]
+    fn split() {
+        let l = Line::new(0., 0., 3., 0.);
+        let pieces = l.split(vec![
+            Point2D::new(1., 0.), Point2D::new(1.0000001, 0.), Point2D::new(2.9999999, 0.),
+        ], 0.000001);
+
+        assert_eq!(pieces.len(), 2);
+        assert_eq!((pieces[0].p1, pieces[0].p2), (Point2D::new(0., 0.), Point2D::new(1., 0.)));
+        assert_eq!((pieces[1].p1, pieces[1].p2), (Point2D::new(1., 0.), Point2D::new(3., 0.)));
+    }
 }
@@ -0,0 +1,96 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// Matches up points that are close enough to count as the same, for the
+// writers that share points between curves. The ends of arcs come from
+// cos and sin, so they're rarely exactly where the lines they meet end.
+
+use std::collections::HashMap;
+use euclid::Point2D;
+
+// Which max_dist-sized square a point is in
+type Cell = (i64, i64);
+
+type Entry<T> = (Point2D<f64, f64>, T);
+
+/// Points, each with something attached, looked up by distance.
+pub struct PointSet<T> {
+    max_dist: f64,
+
+    // Points by the max_dist-sized square they're in, so a lookup only has
+    // to look in the squares around it. With a max_dist of zero, each point
+    // is its own square.
+    cells: HashMap<Cell, Vec<Entry<T>>>,
+}
+
+impl<T> PointSet<T> {
+    /// Points no more than max_dist apart count as the same. Zero or less
+    /// means only exactly equal points do.
+    pub fn new(max_dist: f64) -> PointSet<T> {
+        PointSet { max_dist: max_dist.max(0.0), cells: HashMap::new() }
+    }
+
+    fn cell(&self, p: Point2D<f64, f64>) -> Cell {
+        if self.max_dist == 0.0 {
+            // Adding 0 turns -0 into 0, so the two land together
+            ((p.x + 0.0).to_bits() as i64, (p.y + 0.0).to_bits() as i64)
+        }
+        else {
+            ((p.x / self.max_dist).floor() as i64, (p.y / self.max_dist).floor() as i64)
+        }
+    }
+
+    /// Gets whatever is attached to the closest point within max_dist of p.
+    pub fn get(&self, p: Point2D<f64, f64>) -> Option<&T> {
+        let (x, y) = self.cell(p);
+        let reach = if self.max_dist == 0.0 { 0 } else { 1 };
+
+        (-reach..=reach)
+            .flat_map(|dx| (-reach..=reach).map(move |dy| (x.wrapping_add(dx), y.wrapping_add(dy))))
+            .filter_map(|key| self.cells.get(&key))
+            .flatten()
+            .map(|(q, value)| ((*q - p).length(), value))
+            .filter(|(dist, _)| *dist <= self.max_dist)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, value)| value)
+    }
+
+    pub fn insert(&mut self, p: Point2D<f64, f64>, value: T) {
+        let key = self.cell(p);
+        self.cells.entry(key).or_default().push((p, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        let mut points = PointSet::new(0.1);
+        points.insert(Point2D::new(0.0, 0.0), 1);
+        points.insert(Point2D::new(0.95, 0.0), 2);
+
+        // Close points in the next square over are still found
+        assert_eq!(points.get(Point2D::new(1.02, 0.0)), Some(&2));
+        assert_eq!(points.get(Point2D::new(-0.05, 0.05)), Some(&1));
+        assert_eq!(points.get(Point2D::new(0.5, 0.0)), None);
+
+        let mut exact = PointSet::new(0.0);
+        exact.insert(Point2D::new(-0.0, 1.0), 1);
+        assert_eq!(exact.get(Point2D::new(0.0, 1.0)), Some(&1));
+        assert_eq!(exact.get(Point2D::new(0.0, 1.0000001)), None);
+    }
+}
@@ -0,0 +1,187 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// A record of what splitting did, for checking the result without having
+// to diff the files by hand.
+
+use std::fmt;
+use std::collections::{BTreeMap, HashSet};
+use serde::Serialize;
+use euclid::Point2D;
+use super::part::curve::Curve;
+
+/// A point as [x, y]
+pub type Point = [f64; 2];
+
+#[derive(Debug, Default, Serialize)]
+pub struct SplitReport {
+    /// Every pair of parts that overlapped somewhere
+    pub pairs: Vec<PairReport>,
+    pub totals: Totals,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PairReport {
+    pub levels: [u64; 2],
+
+    /// Number of places where lines of the two parts overlapped
+    pub overlaps: usize,
+
+    /// Lines that had to be broken up because of it
+    pub lines: Vec<LineSplit>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LineSplit {
+    pub level: u64,
+
+    /// The label the line had in the file it was read from, if any
+    pub label: Option<String>,
+
+    pub original: [Point; 2],
+
+    /// Where the line was broken, in order from one end to the other
+    pub split_points: Vec<Point>,
+
+    pub pieces: Vec<[Point; 2]>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct Totals {
+    pub pairs: usize,
+    pub overlaps: usize,
+    pub lines_split: usize,
+    pub split_points: usize,
+    pub pieces: usize,
+}
+
+fn point(p: Point2D<f64, f64>) -> Point {
+    [p.x, p.y]
+}
+
+impl LineSplit {
+    pub fn new(level: u64, original: &Curve, pieces: &[Curve]) -> LineSplit {
+        LineSplit {
+            level,
+            label: original.label().map(String::from),
+            original: [point(original.start()), point(original.end())],
+            split_points: pieces.iter().skip(1).map(|c| point(c.start())).collect(),
+            pieces: pieces.iter().map(|c| [point(c.start()), point(c.end())]).collect(),
+        }
+    }
+}
+
+impl SplitReport {
+    /// Adds a pair, if anything happened between the two parts.
+    pub fn add(&mut self, pair: PairReport) {
+        if pair.overlaps == 0 {
+            return;
+        }
+
+        self.totals.pairs += 1;
+        self.totals.overlaps += pair.overlaps;
+        self.totals.lines_split += pair.lines.len();
+        for l in &pair.lines {
+            self.totals.split_points += l.split_points.len();
+            self.totals.pieces += l.pieces.len();
+        }
+
+        self.pairs.push(pair);
+    }
+
+    /// Number of distinct points that lines were broken at. Lines from
+    /// both parts get broken at the same points, so this is usually
+    /// fewer than the split points in the totals.
+    pub fn new_vertices(&self) -> usize {
+        self.split_points().len()
+    }
+
+    /// The distinct points that lines were broken at, in the order they
+    /// were first broken at.
+    pub fn split_points(&self) -> Vec<Point> {
+        let mut seen = HashSet::new();
+
+        self.pairs.iter()
+            .flat_map(|p| &p.lines)
+            .flat_map(|l| &l.split_points)
+            .filter(|p| seen.insert((p[0].to_bits(), p[1].to_bits())))
+            .copied()
+            .collect()
+    }
+
+    /// Number of lines broken up in each level
+    pub fn lines_split_by_level(&self) -> BTreeMap<u64, usize> {
+        let mut result = BTreeMap::new();
+        for l in self.pairs.iter().flat_map(|p| &p.lines) {
+            *result.entry(l.level).or_insert(0) += 1;
+        }
+
+        result
+    }
+}
+
+impl fmt::Display for SplitReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let t = &self.totals;
+        writeln!(f, "{} line(s) split into {} piece(s), with {} new vertices",
+                 t.lines_split, t.pieces, self.new_vertices())?;
+
+        writeln!(f, "{} interface(s):", t.pairs)?;
+        for p in &self.pairs {
+            writeln!(f, "  LEVEL/{} and LEVEL/{}: {} overlap(s), {} line(s) split",
+                     p.levels[0], p.levels[1], p.overlaps, p.lines.len())?;
+        }
+
+        writeln!(f, "Lines split per part:")?;
+        for (level, count) in self.lines_split_by_level() {
+            writeln!(f, "  LEVEL/{}: {}", level, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::ops::AddAssign for Totals {
+    fn add_assign(&mut self, other: Totals) {
+        self.pairs += other.pairs;
+        self.overlaps += other.overlaps;
+        self.lines_split += other.lines_split;
+        self.split_points += other.split_points;
+        self.pieces += other.pieces;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::part::line::Line;
+
+    #[test]
+    fn line_split() {
+        let original = Curve::from(Line::new(0., 0., 2., 0.));
+        let pieces = vec![Line::new(0., 0., 1., 0.).into(), Line::new(1., 0., 2., 0.).into()];
+        let split = LineSplit::new(20, &original, &pieces);
+
+        assert_eq!(split.split_points, vec![[1., 0.]]);
+        assert_eq!(split.pieces.len(), 2);
+
+        let mut report = SplitReport::default();
+        report.add(PairReport { levels: [1, 20], overlaps: 1, lines: vec![split] });
+        report.add(PairReport { levels: [1, 38], overlaps: 0, lines: Vec::new() });
+
+        assert_eq!(report.pairs.len(), 1);
+        assert_eq!(report.totals.pieces, 2);
+    }
+}
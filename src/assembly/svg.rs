@@ -0,0 +1,290 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// Draws assemblies as SVG, for looking at in a browser.
+//
+// Everything is drawn in model coordinates inside a group that flips the
+// y axis, so y goes up like it does in the NFL file. Strokes don't scale
+// with the drawing, so lines stay the same width however big the model is.
+// Hovering over anything shows what it is.
+
+use euclid::Point2D;
+use super::{Assembly, Margin};
+use super::color::Color;
+use super::draw::{escape, view_bounds};
+use super::nfl::{self, Labeler, WriteOptions};
+use super::part::line::Line;
+use super::part::arc::Arc;
+use super::part::curve::Curve;
+use super::report::Point;
+
+const BACKGROUND: &str = "#202020";
+const DEFAULT_COLOR: &str = "#ffffff";
+const SPLIT_COLOR: &str = "#ff8000";
+
+// Stroke widths in pixels
+const CURVE_WIDTH: f64 = 1.5;
+const SHARED_WIDTH: f64 = 4.0;
+const SPLIT_POINT_SIZE: f64 = 9.0;
+
+// Width of the image in pixels; the height follows from the model
+const IMAGE_WIDTH: f64 = 1000.0;
+
+/// Gets the color to draw a part in, after its COLOR/. These are brighter
+/// than the plain CSS colors so they show up against the dark background.
+fn color(color: Option<Color>) -> &'static str {
+    match color {
+        Some(Color::Red) => "#ff5050",
+        Some(Color::Yellow) => "#ffff50",
+        Some(Color::Green) => "#50ff50",
+        Some(Color::Cyan) => "#50ffff",
+        Some(Color::Blue) => "#7070ff",
+        Some(Color::Magenta) => "#ff50ff",
+        Some(Color::White) => "#ffffff",
+        None => DEFAULT_COLOR,
+    }
+}
+
+fn part_name(level: u64, opts: &WriteOptions) -> String {
+    match opts.level_names.get(&level) {
+        Some(name) => format!("LEVEL/{} ({})", level, name),
+        None => format!("LEVEL/{}", level),
+    }
+}
+
+/// The path data for a curve, or None for circles, which are drawn as
+/// <circle>s instead.
+fn path_data(c: &Curve) -> Option<String> {
+    match c {
+        Curve::Line(l) => Some(format!("M {} {} L {} {}", l.p1().x, l.p1().y, l.p2().x, l.p2().y)),
+        Curve::Arc(a) if a.is_circle() => None,
+        Curve::Arc(a) => {
+            let (start, end) = (a.start(), a.end());
+            let large = if a.sweep() > 180.0 { 1 } else { 0 };
+
+            // The y axis is flipped, so counterclockwise is the positive
+            // angle direction here too
+            Some(format!("M {} {} A {} {} 0 {} 1 {} {}",
+                         start.x, start.y, a.radius, a.radius, large, end.x, end.y))
+        },
+    }
+}
+
+fn draw(c: &Curve, attrs: &str, title: &str) -> String {
+    match path_data(c) {
+        Some(d) => format!("<path d=\"{}\" {}><title>{}</title></path>", d, attrs, escape(title)),
+        None => {
+            let a = c.as_arc().expect("Only circles have no path");
+            format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\" {}><title>{}</title></circle>",
+                    a.center.x, a.center.y, a.radius, attrs, escape(title))
+        },
+    }
+}
+
+fn overlaps(c: &Curve, point: &Point2D<f64, f64>, max_dist: f64) -> bool {
+    match c {
+        Curve::Line(l) => l.overlaps(point, max_dist),
+        Curve::Arc(a) => a.overlaps(point, max_dist),
+    }
+}
+
+/// Checks if all of a lies on b.
+fn lies_along(a: &Curve, b: &Curve, max_dist: f64) -> bool {
+    if let (Curve::Arc(a), Curve::Arc(b)) = (a, b) {
+        if a.is_circle() && !b.is_circle() {
+            return false;
+        }
+    }
+
+    [a.start(), a.point_at(0.5), a.end()].iter().all(|p| overlaps(b, p, max_dist))
+}
+
+/// Gets the stretches that a and b have in common. Once an assembly has
+/// been split these are whole curves; before, they can be parts of them.
+fn shared(a: &Curve, b: &Curve, max_dist: f64) -> Vec<Curve> {
+    if lies_along(a, b, max_dist) {
+        vec![a.clone()]
+    }
+    else if lies_along(b, a, max_dist) {
+        vec![b.clone()]
+    }
+    else {
+        Curve::find_overlaps(a, b, max_dist).into_iter()
+            .filter(|(p1, p2)| (*p2 - *p1).length() > max_dist)
+            .map(|(p1, p2)| match a {
+                Curve::Line(_) => Curve::from(Line::from_points(p1, p2)),
+                Curve::Arc(a) => Curve::from(Arc::from_angles(a.center, a.radius, a.angle_of(&p1), a.angle_of(&p2))),
+            })
+            .collect()
+    }
+}
+
+/// Draws the assembly as SVG. Each part is drawn in its COLOR/, stretches
+/// shared by two parts are drawn thicker, and the given split points are
+/// marked with dots. Hovering shows the level and the label each curve
+/// gets when written as NFL with the same options.
+pub fn write<M: Margin>(asm: &Assembly, opts: &WriteOptions, line_overlap_margin: M,
+                        split_points: &[Point]) -> String
+{
+    let bounds = view_bounds(asm);
+    let height = IMAGE_WIDTH * bounds.height() / bounds.width();
+
+    let mut result = Vec::new();
+    result.push(String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+    result.push(format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{:.0}\" viewBox=\"{} {} {} {}\">",
+        IMAGE_WIDTH, height, bounds.min.x, -bounds.max.y, bounds.width(), bounds.height()
+    ));
+    result.push(format!(
+        "<style>path, circle {{ fill: none; vector-effect: non-scaling-stroke; stroke-linecap: round }} \
+         .curve {{ stroke-width: {}px }} .shared {{ stroke-width: {}px; stroke-opacity: 0.6 }} \
+         .split {{ stroke: {}; stroke-width: {}px }} .curve:hover, .shared:hover {{ stroke-width: {}px }}</style>",
+        CURVE_WIDTH, SHARED_WIDTH, SPLIT_COLOR, SPLIT_POINT_SIZE, SHARED_WIDTH * 2.0
+    ));
+    result.push(format!(
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+        bounds.min.x, -bounds.max.y, bounds.width(), bounds.height(), BACKGROUND
+    ));
+    result.push(String::from("<g transform=\"scale(1, -1)\">"));
+
+    let mut labeler = Labeler::new(opts.label_scheme);
+    for p in &asm.parts {
+        let name = part_name(p.level, opts);
+
+        result.push(format!("<g id=\"level-{}\" stroke=\"{}\">", p.level, color(p.color())));
+        for c in &p.curves {
+            let label = nfl::format_label(c.prefix(), labeler.next(c.prefix(), p.level), opts);
+            result.push(draw(c, "class=\"curve\"", &format!("{} {}", name, label)));
+        }
+        result.push(String::from("</g>"));
+    }
+
+    // Drawn over the parts so they can be hovered over, half see-through so
+    // the colors of both parts show
+    for (i, a) in asm.parts.iter().enumerate() {
+        for b in &asm.parts[i + 1..] {
+            let max_dist = line_overlap_margin.between(a.level, b.level);
+            let title = format!("Shared by {} and {}", part_name(a.level, opts), part_name(b.level, opts));
+
+            for ca in &a.curves {
+                for cb in &b.curves {
+                    for s in shared(ca, cb, max_dist) {
+                        let attrs = format!("class=\"shared\" stroke=\"{}\"", color(a.color()));
+                        result.push(draw(&s, &attrs, &title));
+                    }
+                }
+            }
+        }
+    }
+
+    // A zero-length path with round caps draws as a dot that stays the same
+    // size however far in the drawing is zoomed
+    for p in split_points {
+        result.push(format!(
+            "<path d=\"M {} {} h 0\" class=\"split\"><title>Split at ({}, {})</title></path>",
+            p[0], p[1], p[0], p[1]
+        ));
+    }
+
+    result.push(String::from("</g>"));
+    result.push(String::from("</svg>"));
+
+    result.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn svg() {
+        let mut asm = Assembly::empty();
+        asm.add_part(1, vec![("COLOR", "GREEN")])
+            .add_line(Point2D::new(0., 0.), Point2D::new(2., 0.))
+            .add_circle(Point2D::new(1., 1.), 0.5);
+        asm.add_part(2, vec![("COLOR", "1")])
+            .add_line(Point2D::new(1., 0.), Point2D::new(3., 0.));
+
+        let svg = write(&asm, &WriteOptions::default(), 0.000001, &[[1., 0.]]);
+
+        assert!(svg.contains("<g id=\"level-1\" stroke=\"#50ff50\">"));
+        assert!(svg.contains("<g id=\"level-2\" stroke=\"#ff5050\">"));
+        assert!(svg.contains("<title>LEVEL/1 L00001</title>"));
+        assert!(svg.contains("<circle cx=\"1\" cy=\"1\" r=\"0.5\" class=\"curve\"><title>LEVEL/1 C00002</title>"));
+
+        // Only the stretch from 1 to 2 is shared
+        assert_eq!(svg.matches("class=\"shared\"").count(), 1);
+        assert!(svg.contains("<path d=\"M 1 0 L 2 0\" class=\"shared\""));
+        assert!(svg.contains("<path d=\"M 1 0 h 0\" class=\"split\">"));
+    }
+
+    #[test]
+    fn large_arcs() {
+        let arc = |go, end| path_data(&Curve::from(Arc::from_angles(Point2D::new(0., 0.), 1., go, end))).unwrap();
+
+        assert_eq!(arc(0., 90.), "M 1 0 A 1 1 0 0 1 0 1");
+        assert_eq!(arc(0., 180.), "M 1 0 A 1 1 0 0 1 -1 0");
+        assert_eq!(arc(0., 270.), "M 1 0 A 1 1 0 1 1 0 -1");
+
+        // Going through 0 is still under half a circle
+        assert_eq!(arc(270., 90.), "M 0 -1 A 1 1 0 0 1 0 1");
+        assert_eq!(arc(90., 0.), "M 0 1 A 1 1 0 1 1 1 0");
+    }
+
+    #[test]
+    fn bounds() {
+        let view_box = |asm: &Assembly| {
+            let svg = write(asm, &WriteOptions::default(), 0.000001, &[]);
+            let start = svg.find("viewBox=\"").unwrap() + 9;
+            svg[start..].split('"').next().unwrap().to_string()
+        };
+
+        // Nothing to draw gets a unit square
+        assert_eq!(view_box(&Assembly::empty()), "-0.05 -1.05 1.1 1.1");
+
+        // A flat drawing still gets some height
+        let mut asm = Assembly::empty();
+        asm.add_part(1, vec![("COLOR", "GREEN")]).add_line(Point2D::new(0., 0.), Point2D::new(2., 0.));
+        assert_eq!(view_box(&asm), "-0.1 -0.1 2.2 0.2");
+
+        // And a drawing that's all one point gets some size
+        let mut asm = Assembly::empty();
+        asm.add_part(1, vec![("COLOR", "GREEN")]).add_line(Point2D::new(1., 1.), Point2D::new(1., 1.));
+        let svg = write(&asm, &WriteOptions::default(), 0.000001, &[]);
+        assert!(!svg.contains("NaN") && !svg.contains("inf"));
+        assert!((view_bounds(&asm).width() - 2e-6).abs() < 1e-12);
+        assert!(svg.contains("width=\"1000\" height=\"1000\""));
+    }
+
+    #[test]
+    fn shared_curves() {
+        let half = Curve::from(Arc::from_angles(Point2D::new(0., 0.), 1., 0., 180.));
+        let circle = Curve::from(Arc::circle(Point2D::new(0., 0.), 1.));
+
+        assert_eq!(shared(&half, &circle, 0.000001).len(), 1);
+        assert!(shared(&half, &circle, 0.000001)[0].as_arc().unwrap().sweep() == 180.);
+
+        // Arcs that only partly overlap share the stretch from 90 to 180
+        let other = Curve::from(Arc::from_angles(Point2D::new(0., 0.), 1., 90., 270.));
+        let s = shared(&half, &other, 0.000001);
+        assert_eq!(s.len(), 1);
+        assert_eq!((s[0].as_arc().unwrap().go_angle, s[0].as_arc().unwrap().end_angle), (90., 180.));
+
+        let a = Curve::from(Line::new(0., 0., 1., 0.));
+        let b = Curve::from(Line::new(1., 0., 0., 0.));
+        assert_eq!(shared(&a, &b, 0.000001).len(), 1);
+        assert!(shared(&a, &Curve::from(Line::new(0., 1., 1., 1.)), 0.000001).is_empty());
+    }
+}
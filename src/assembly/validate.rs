@@ -0,0 +1,67 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// Checks for things that would stop the geometry from meshing properly.
+
+use std::fmt;
+use super::{Assembly, Margin};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// Two parts overlap somewhere without sharing endpoints
+    Overlap { levels: [u64; 2] },
+
+    /// A line whose ends are the same point, give or take the margin
+    ZeroLength { level: u64, line: String },
+
+    /// An arc with no radius
+    ZeroRadius { level: u64, arc: String },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Overlap { levels } =>
+                write!(f, "LEVEL/{} and LEVEL/{} overlap without sharing endpoints",
+                       levels[0], levels[1]),
+            Problem::ZeroLength { level, line } =>
+                write!(f, "LEVEL/{}: {} has no length", level, line),
+            Problem::ZeroRadius { level, arc } =>
+                write!(f, "LEVEL/{}: {} has no radius", level, arc),
+        }
+    }
+}
+
+/// Finds every problem in the assembly. An empty list means the assembly
+/// is split and ready to mesh.
+pub fn validate<M: Margin>(asm: &Assembly, margin: M) -> Vec<Problem> {
+    let mut result: Vec<Problem> = asm.unresolved_overlaps(|a, b| margin.between(a, b)).into_iter()
+        .map(|(a, b)| Problem::Overlap { levels: [a, b] })
+        .collect();
+
+    for p in &asm.parts {
+        let max_dist = margin.between(p.level, p.level);
+
+        for l in p.lines().filter(|l| l.to_vector().length() <= max_dist) {
+            result.push(Problem::ZeroLength { level: p.level, line: l.to_string() });
+        }
+
+        for a in p.arcs().filter(|a| a.radius <= 0.) {
+            result.push(Problem::ZeroRadius { level: p.level, arc: a.to_string() });
+        }
+    }
+
+    result
+}
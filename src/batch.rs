@@ -0,0 +1,328 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// Runs a command over many files at once, then sums up how it went.
+//
+// Files are processed in parallel, and one file failing doesn't stop the
+// others; the failure just shows up in the table at the end.
+
+use std::fs;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use rayon::prelude::*;
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::args::{InputOpts, OutputOpts, Destination};
+use crate::diag::{self, Failure, Status};
+use nfl2mac::assembly::Assembly;
+use nfl2mac::assembly::report::{SplitReport, Totals};
+
+/// How processing a single file went.
+#[derive(Debug)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub lines: usize,
+    pub arcs: usize,
+
+    /// Number of lines that were broken up
+    pub splits: usize,
+
+    /// What splitting did, if the file was split
+    pub split: Option<SplitReport>,
+
+    pub warnings: Vec<String>,
+    pub error: Option<Failure>,
+
+    /// File the result was written to, if any
+    pub written: Option<PathBuf>,
+
+    /// What ended up being written, if it was anything worth comparing
+    pub result: Option<Assembly>,
+}
+
+impl FileReport {
+    pub fn new(path: &Path) -> FileReport {
+        FileReport {
+            path: path.to_path_buf(),
+            lines: 0,
+            arcs: 0,
+            splits: 0,
+            split: None,
+            warnings: Vec::new(),
+            error: None,
+            written: None,
+            result: None,
+        }
+    }
+
+    /// Records how many entities are in the assembly.
+    pub fn count(&mut self, asm: &Assembly) {
+        self.lines = asm.parts.iter().map(|p| p.lines().count()).sum();
+        self.arcs = asm.parts.iter().map(|p| p.arcs().count()).sum();
+    }
+
+    pub fn record_split(&mut self, split: SplitReport) {
+        self.splits = split.totals.lines_split;
+        self.split = Some(split);
+    }
+
+    pub fn warn<S: Into<String>>(&mut self, warning: S) {
+        self.warnings.push(warning.into());
+    }
+}
+
+/// What gets done to each file. It's given the output options to use,
+/// since those aren't always the ones from the command line.
+pub trait Work: Fn(&Path, &OutputOpts, &mut FileReport) -> Result<(), Box<dyn Error>> + Sync {}
+
+impl<F> Work for F
+where F: Fn(&Path, &OutputOpts, &mut FileReport) -> Result<(), Box<dyn Error>> + Sync {}
+
+/// Where a command puts its result for a file when neither --output nor
+/// --output-dir is given, such as `OutputOpts::split_dest`.
+pub trait DefaultDest: Fn(&Path, &OutputOpts) -> Result<PathBuf, Failure> + Sync {}
+
+impl<F> DefaultDest for F
+where F: Fn(&Path, &OutputOpts) -> Result<PathBuf, Failure> + Sync {}
+
+/// Runs `work` on every input file, and prints a summary if there was more
+/// than one. Fails if any of the files failed.
+pub fn run<D: DefaultDest, F: Work>(input: &InputOpts, output: &OutputOpts, dest: D, work: F)
+    -> Result<(), Box<dyn Error>>
+{
+    // Better to find out now than after every file has been done.
+    if let Some(path) = output.report.as_deref().filter(|p| p.exists() && !output.force) {
+        return Err(already_exists(path).into());
+    }
+
+    let reports = process(input, output, &dest, &work)?;
+
+    if let Some(path) = &output.report {
+        write_split_report(path, &reports, output.force)?;
+    }
+
+    summarize(&reports)
+}
+
+/// Runs `work` on every input file, without saying how it went. Nothing is
+/// done if two of the files would be written to the same place.
+pub fn process<D: DefaultDest, F: Work>(input: &InputOpts, output: &OutputOpts, dest: &D, work: &F)
+    -> Result<Vec<FileReport>, Box<dyn Error>>
+{
+    let files = input.files()?;
+
+    if files.is_empty() {
+        return Err(Failure::new(Status::Error, "No files to process").into());
+    }
+
+    if files.len() > 1 && output.output.is_some() {
+        return Err(Failure::new(
+            Status::Error, "--output only works with one input file; use --output-dir instead"
+        ).into());
+    }
+
+    let dests: Vec<(&Path, PathBuf)> = files.iter()
+        .filter_map(|f| match dest(f, output).and_then(|d| output.dest(d)) {
+            Ok(Destination::File(d)) => Some(Ok((f.as_path(), d))),
+            Ok(Destination::Stdout) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<Result<_, Failure>>()?;
+
+    if let Some((a, b, d)) = same_dest(&dests) {
+        return Err(Failure::new(Status::Error, format!(
+            "{} and {} would both be written to {}; rename one of them or run them separately",
+            a.display(), b.display(), d.display()
+        )).into());
+    }
+
+    // 0 threads means one per core
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(input.jobs.unwrap_or(0))
+        .build()?;
+
+    let reports: Vec<FileReport> = pool.install(|| {
+        files.par_iter().map(|path| {
+            let mut report = FileReport::new(path);
+
+            if let Err(e) = work(path, output, &mut report) {
+                report.error = Some(Failure::classify(e.as_ref()));
+            }
+
+            report
+        }).collect()
+    });
+
+    Ok(reports)
+}
+
+/// Finds two files that would be written to the same place, given each
+/// input with its destination. Names are compared ignoring case, since
+/// they'd clash on Windows and macOS.
+fn same_dest<'a>(dests: &'a [(&'a Path, PathBuf)]) -> Option<(&'a Path, &'a Path, &'a Path)> {
+    let mut seen: HashMap<String, &Path> = HashMap::new();
+
+    for (input, dest) in dests {
+        if let Some(other) = seen.insert(dest.to_string_lossy().to_lowercase(), input) {
+            return Some((other, input, dest));
+        }
+    }
+
+    None
+}
+
+/// Writes a file, making the directory it goes in if needed. Existing files
+/// are only overwritten if forced to.
+pub fn write_file(path: &Path, bytes: &[u8], force: bool) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .create_new(!force)
+        .open(path);
+
+    match file {
+        Ok(mut f) => Ok(f.write_all(bytes)?),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(already_exists(path).into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn already_exists(path: &Path) -> Failure {
+    Failure::new(Status::Io, format!("{:?} already exists; use --force to overwrite it", path))
+}
+
+/// Writes what splitting did to every file as JSON. An existing report is
+/// only overwritten if forced to.
+pub fn write_split_report(path: &Path, reports: &[FileReport], force: bool) -> Result<(), Box<dyn Error>> {
+    #[derive(Serialize)]
+    struct FileSplits<'a> {
+        file: &'a Path,
+        #[serde(flatten)]
+        split: &'a SplitReport,
+    }
+
+    #[derive(Serialize)]
+    struct Report<'a> {
+        files: Vec<FileSplits<'a>>,
+        totals: Totals,
+    }
+
+    let mut report = Report { files: Vec::new(), totals: Totals::default() };
+    for r in reports {
+        if let Some(split) = &r.split {
+            report.totals += split.totals;
+            report.files.push(FileSplits { file: &r.path, split });
+        }
+    }
+
+    let json = serde_json::to_string_pretty(&report)?;
+    write_file(path, (json + "\n").as_bytes(), force)
+}
+
+/// Prints how processing went, or just the problems if there was only one
+/// file. Fails if any of the files failed, the same way the first one did.
+pub fn summarize(reports: &[FileReport]) -> Result<(), Box<dyn Error>> {
+    let single = reports.len() == 1;
+
+    // The table already shows the problems with each file
+    let table = !single && diag::is_text() && !diag::quiet();
+
+    for r in reports {
+        for w in &r.warnings {
+            if table {
+                diag::count_warning();
+            }
+            else {
+                diag::warn(Some(&r.path), w);
+            }
+        }
+
+        match &r.error {
+            Some(e) if single => return Err(e.clone().into()),
+            Some(e) if !table => diag::error(Some(&r.path), e.status, &e.message),
+            _ => {},
+        }
+    }
+
+    if table {
+        print_table(reports);
+    }
+
+    let failed: Vec<&Failure> = reports.iter().filter_map(|r| r.error.as_ref()).collect();
+    match failed.first() {
+        Some(first) => Err(Failure::new(
+            first.status, format!("{} of {} files failed", failed.len(), reports.len())
+        ).into()),
+        None => Ok(()),
+    }
+}
+
+fn print_table(reports: &[FileReport]) {
+    let name_width = reports.iter()
+        .map(|r| r.path.display().to_string().len())
+        .chain(std::iter::once(4))
+        .max()
+        .unwrap();
+
+    println!("{:<w$}  {:>6}  {:>6}  {:>6}  {:>8}  Status", "File", "Lines", "Arcs", "Splits",
+             "Warnings", w = name_width);
+
+    for r in reports {
+        let status = match &r.error {
+            Some(e) => format!("{} {}", "FAILED".red(), e),
+            None if !r.warnings.is_empty() => format!("{} {}", "OK".yellow(), r.warnings.join("; ")),
+            None => format!("{}", "OK".green()),
+        };
+
+        println!("{:<w$}  {:>6}  {:>6}  {:>6}  {:>8}  {}", r.path.display(), r.lines, r.arcs,
+                 r.splits, r.warnings.len(), status, w = name_width);
+    }
+
+    let total = |f: fn(&FileReport) -> usize| reports.iter().map(f).sum::<usize>();
+    println!("{:<w$}  {:>6}  {:>6}  {:>6}  {:>8}  {} of {} failed", "Total",
+             total(|r| r.lines), total(|r| r.arcs), total(|r| r.splits),
+             total(|r| r.warnings.len()), total(|r| r.error.is_some() as usize),
+             reports.len(), w = name_width);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_names() {
+        let dests = |pairs: &[(&'static str, &str)]| -> Vec<(&'static Path, PathBuf)> {
+            pairs.iter().map(|(input, dest)| (Path::new(*input), PathBuf::from(dest))).collect()
+        };
+
+        let ok = dests(&[("a/model.nfl", "out/model.mac"), ("a/other.nfl", "out/other.mac")]);
+        assert_eq!(same_dest(&ok), None);
+
+        let clash = dests(&[("a/model.nfl", "a/model-split.nfl"), ("a/model.dxf", "a/model-split.nfl")]);
+        assert_eq!(same_dest(&clash), Some((Path::new("a/model.nfl"), Path::new("a/model.dxf"),
+                                            Path::new("a/model-split.nfl"))));
+
+        let clash = dests(&[("Model.nfl", "out/Model.mac"), ("b/model.NFL", "out/model.mac")]);
+        assert!(same_dest(&clash).is_some());
+    }
+}
@@ -0,0 +1,288 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// One function per subcommand. These are the glue between the command line
+// options in args and the actual work in assembly.
+
+use std::{error::Error, path::Path};
+use std::io::{self, Write};
+use log::{info, debug};
+
+use crate::args::{InputOpts, OutputOpts, FileType, OutputFormat, InputFormat, Destination};
+use nfl2mac::assembly::{Assembly, diff};
+use nfl2mac::assembly::encoding::{TextFormat, TextEncoding};
+use crate::batch::{self, FileReport};
+use crate::diag::{self, Failure, Status};
+use crate::watch;
+
+pub fn split(input: &InputOpts, output: &OutputOpts, watch: bool, dry_run: bool)
+    -> Result<(), Box<dyn Error>>
+{
+    let work = |target: &Path, output: &OutputOpts, report: &mut FileReport| {
+        split_file(target, input, output, dry_run, report)
+    };
+    let dest = |target: &Path, output: &OutputOpts| output.split_dest(target);
+
+    if watch {
+        watch::run(input, output, dest, work)
+    }
+    else if dry_run {
+        let reports = batch::process(input, output, &dest, &work)?;
+
+        for r in &reports {
+            if let (Some(split), false) = (&r.split, diag::quiet()) {
+                println!("{}: would split", r.path.display());
+                print!("{}", split);
+                println!();
+            }
+        }
+
+        batch::summarize(&reports)
+    }
+    else {
+        batch::run(input, output, dest, work)
+    }
+}
+
+fn split_file(target: &Path, input: &InputOpts, output: &OutputOpts, dry_run: bool,
+              report: &mut FileReport)
+    -> Result<(), Box<dyn Error>>
+{
+    let file_type = input.target_type(target)?;
+    let mut asm = input.read_as(target, file_type.input_format())?;
+    let opts = output.write_options(input, &asm);
+    report.count(&asm);
+    debug!("File contents:\n{}", asm.to_nfl(&opts));
+
+    match file_type {
+        FileType::RawNFL | FileType::RawDXF => {
+            let dest = output.dest(output.split_dest(target)?)?;
+            info!("Raw file... will split and store in {:?}", dest);
+            report.record_split(asm.split(|a, b| input.precision(a, b)));
+            report.count(&asm);
+            debug!("After split:\n{}", asm.to_nfl(&opts));
+            asm.check_labels(&opts)?;
+
+            if dry_run {
+                if let Destination::File(path) = &dest {
+                    if path.exists() && !output.force {
+                        report.warn(format!("{:?} already exists; use --force to overwrite it", path));
+                    }
+                }
+
+                return Ok(());
+            }
+
+            write(&dest, &asm.to_bytes(&opts), output.force, report)?;
+            report.result = Some(asm);
+        },
+
+        FileType::SplitNFL => {
+            report.warn("Already split... not splitting");
+        },
+    }
+
+    Ok(())
+}
+
+pub fn convert(input: &InputOpts, output: &OutputOpts, to: OutputFormat, from: InputFormat,
+               split: bool, watch: bool)
+    -> Result<(), Box<dyn Error>>
+{
+    let work = |target: &Path, output: &OutputOpts, report: &mut FileReport| {
+        convert_file(target, input, output, to, from, split, report)
+    };
+    let dest = |target: &Path, output: &OutputOpts| output.convert_dest(target, to);
+
+    if watch {
+        watch::run(input, output, dest, work)
+    }
+    else {
+        batch::run(input, output, dest, work)
+    }
+}
+
+fn convert_file(target: &Path, input: &InputOpts, output: &OutputOpts, to: OutputFormat,
+                from: InputFormat, split: bool, report: &mut FileReport)
+    -> Result<(), Box<dyn Error>>
+{
+    let from = match from {
+        InputFormat::Nfl => input.target_type(target)?.input_format(),
+        _ => from,
+    };
+
+    let mut asm = input.read_as(target, from)?;
+    let opts = output.write_options(input, &asm);
+
+    if split {
+        report.record_split(asm.split(|a, b| input.precision(a, b)));
+    }
+    report.count(&asm);
+
+    let dest = output.dest(output.convert_dest(target, to)?)?;
+    info!("Converting to {:?} and storing in {:?}", to, dest);
+
+    let split_points = report.split.as_ref().map(|s| s.split_points()).unwrap_or_default();
+
+    let bytes = match to {
+        OutputFormat::Nfl => {
+            asm.check_labels(&opts)?;
+            asm.to_bytes(&opts)
+        },
+
+        // ANSYS doesn't want the SUB at the end
+        OutputFormat::Mac => TextFormat { eof_marker: false, ..opts.text.clone() }
+            .encode(&asm.to_mac(&opts)),
+
+        // These are for other programs, which will want plain UTF-8
+        OutputFormat::Json => (asm.to_json() + "\n").into_bytes(),
+        OutputFormat::Yaml => asm.to_yaml().into_bytes(),
+
+        OutputFormat::Dxf => {
+            let marked = if output.mark_splits { &split_points[..] } else { &[] };
+            TextFormat { eof_marker: false, ..opts.text.clone() }
+                .encode(&asm.to_dxf(&opts, marked))
+        },
+        OutputFormat::Svg => asm.to_svg(&opts, |a, b| input.precision(a, b), &split_points)
+            .into_bytes(),
+        OutputFormat::Ggb => asm.to_ggb(&opts, &split_points),
+    };
+
+    write(&dest, &bytes, output.force, report)?;
+    report.result = Some(asm);
+
+    Ok(())
+}
+
+/// Writes out a result. Existing files are only overwritten if forced to.
+fn write(dest: &Destination, bytes: &[u8], force: bool, report: &mut FileReport)
+    -> Result<(), Box<dyn Error>>
+{
+    match dest {
+        Destination::Stdout => {
+            io::stdout().write_all(bytes)?;
+        },
+
+        Destination::File(path) => {
+            batch::write_file(path, bytes, force)?;
+            report.written = Some(path.clone());
+        },
+    }
+
+    Ok(())
+}
+
+pub fn info(input: &InputOpts) -> Result<(), Box<dyn Error>> {
+    for (i, target) in input.files()?.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        info_file(target, input)?;
+    }
+
+    Ok(())
+}
+
+fn info_file(target: &Path, input: &InputOpts) -> Result<(), Box<dyn Error>> {
+    let file_type = input.target_type(target)?;
+    let asm = input.read_as(target, file_type.input_format())?;
+    let format = &asm.text_format;
+
+    println!("File:      {}", target.display());
+    println!("Type:      {:?}", file_type);
+    println!("Encoding:  {:?}, {:?} line endings{}", format.encoding, format.line_ending,
+             if format.eof_marker { ", SUB at end" } else { "" });
+    let width = asm.detect_label_width();
+    let overflowing = asm.overflowing_labels(width).len();
+    println!("Labels:    {:?}, {} digits{}", asm.detect_label_scheme(), width,
+             if overflowing > 0 { format!(", {} wider", overflowing) } else { String::new() });
+    println!("Parts:     {}", asm.parts.len());
+
+    for p in &asm.parts {
+        let color = p.data.get("COLOR").map(String::as_str).unwrap_or("-");
+        println!("  LEVEL/{:<6} COLOR/{:<8} {:>5} lines {:>5} arcs",
+                 p.level, color, p.lines().count(), p.arcs().count());
+    }
+
+    println!("Total:     {} lines, {} arcs",
+             asm.parts.iter().map(|p| p.lines().count()).sum::<usize>(),
+             asm.parts.iter().map(|p| p.arcs().count()).sum::<usize>());
+
+    Ok(())
+}
+
+pub fn check(input: &InputOpts) -> Result<(), Box<dyn Error>> {
+    let files = input.files()?;
+    let mut failed = Vec::new();
+
+    for target in &files {
+        if let Err(e) = check_file(target, input) {
+            let f = Failure::classify(e.as_ref());
+
+            if files.len() > 1 {
+                diag::error(Some(target), f.status, &f.message);
+            }
+            failed.push(f);
+        }
+    }
+
+    match failed.first() {
+        Some(f) if files.len() == 1 => Err(f.clone().into()),
+        Some(f) => Err(Failure::new(
+            f.status, format!("{} of {} files failed the check", failed.len(), files.len())
+        ).into()),
+        None => Ok(()),
+    }
+}
+
+fn check_file(target: &Path, input: &InputOpts) -> Result<(), Box<dyn Error>> {
+    let asm = input.read_as(target, input.target_type(target)?.input_format())?;
+    let problems = asm.validate(|a, b| input.precision(a, b));
+
+    for problem in &problems {
+        diag::error(Some(target), Status::Geometry, &problem.to_string());
+    }
+
+    if problems.is_empty() {
+        if !diag::quiet() {
+            println!("{}: OK", target.display());
+        }
+        Ok(())
+    }
+    else {
+        Err(Failure::new(Status::Geometry, format!("Found {} problem(s)", problems.len())).into())
+    }
+}
+
+pub fn diff(old: &Path, new: &Path, encoding: Option<TextEncoding>, line_precision: f64)
+    -> Result<(), Box<dyn Error>>
+{
+    let old = Assembly::read(old, encoding)?;
+    let new = Assembly::read(new, encoding)?;
+
+    let differences = diff::diff(&old, &new, line_precision);
+    for d in &differences {
+        println!("{}", d);
+    }
+
+    if differences.is_empty() {
+        info!("No differences");
+        Ok(())
+    }
+    else {
+        Err(Failure::new(Status::Geometry, format!("Found {} difference(s)", differences.len())).into())
+    }
+}
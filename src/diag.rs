@@ -0,0 +1,221 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// Exit codes, and how errors and warnings get reported.
+//
+// Scripts need to tell a bad input from a broken disk, so every failure is
+// sorted into one of a few kinds, each with its own exit code. Errors and
+// warnings are printed as text for people, or as JSON for CI pipelines.
+
+use std::{fmt, io, error::Error};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use serde::Serialize;
+use strum_macros::EnumString;
+use colored::Colorize;
+
+use nfl2mac::assembly::ReadError;
+
+/// What the process exits with. These are documented in the --help text,
+/// so don't renumber them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    /// Everything worked
+    Success = 0,
+
+    /// Bad command line or project file, or anything not covered below
+    Error = 1,
+
+    /// Everything worked, but there were warnings along the way
+    Warnings = 2,
+
+    /// An input file couldn't be understood
+    Parse = 3,
+
+    /// The geometry failed a check, or two files differ
+    Geometry = 4,
+
+    /// A file couldn't be read or written
+    Io = 5,
+}
+
+/// An error, along with what kind of error it is.
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub status: Status,
+    pub message: String,
+}
+
+/// Format of errors and warnings.
+#[derive(Debug, Clone, Copy, PartialEq, EnumString)]
+#[strum(serialize_all = "kebab_case")]
+pub enum Format {
+    Text,
+
+    /// One JSON object per line
+    Json,
+}
+
+#[derive(Serialize)]
+struct Diagnostic<'a> {
+    severity: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<Status>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a Path>,
+    message: &'a str,
+}
+
+#[derive(Serialize)]
+struct Summary {
+    status: Status,
+    exit_code: i32,
+    warnings: usize,
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+impl Failure {
+    pub fn new<S: Into<String>>(status: Status, message: S) -> Failure {
+        Failure { status, message: message.into() }
+    }
+
+    /// Works out what kind of failure an error is, for errors that didn't
+    /// start out as a Failure.
+    pub fn classify(e: &(dyn Error + 'static)) -> Failure {
+        if let Some(f) = e.downcast_ref::<Failure>() {
+            return f.clone();
+        }
+
+        let status = match e.downcast_ref::<ReadError>() {
+            Some(ReadError::Io(_)) => Status::Io,
+            Some(ReadError::Parse(_)) => Status::Parse,
+            None if e.is::<io::Error>() => Status::Io,
+            None => Status::Error,
+        };
+
+        Failure::new(status, e.to_string())
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for Failure {}
+
+impl From<ReadError> for Failure {
+    fn from(e: ReadError) -> Failure {
+        Failure::classify(&e)
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(e: io::Error) -> Failure {
+        Failure::new(Status::Io, e.to_string())
+    }
+}
+
+pub fn init(format: Format, quiet: bool) {
+    JSON.store(format == Format::Json, Ordering::Relaxed);
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Whether anything but errors should be left out
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Whether the output is meant for people, rather than machines
+pub fn is_text() -> bool {
+    !JSON.load(Ordering::Relaxed)
+}
+
+pub fn warnings() -> usize {
+    WARNINGS.load(Ordering::Relaxed)
+}
+
+/// Counts a warning that has already been shown some other way.
+pub fn count_warning() {
+    WARNINGS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn warn(file: Option<&Path>, message: &str) {
+    count_warning();
+
+    if !is_text() {
+        emit(&Diagnostic { severity: "warning", status: None, file, message });
+    }
+    else if !quiet() {
+        eprintln!("{}: {}", "warning".yellow(), with_file(file, message));
+    }
+}
+
+/// Reports an error. These are shown even when quiet.
+pub fn error(file: Option<&Path>, status: Status, message: &str) {
+    if is_text() {
+        eprintln!("{}: {}", "error".red(), with_file(file, message));
+    }
+    else {
+        emit(&Diagnostic { severity: "error", status: Some(status), file, message });
+    }
+}
+
+/// Reports how things went overall, for JSON output. Text output doesn't
+/// need this; the exit code says it all.
+pub fn finish(status: Status) {
+    if !is_text() {
+        emit(&Summary { status, exit_code: status as i32, warnings: warnings() });
+    }
+}
+
+fn with_file(file: Option<&Path>, message: &str) -> String {
+    match file {
+        Some(f) => format!("{}: {}", f.display(), message),
+        None => message.to_string(),
+    }
+}
+
+fn emit<T: Serialize>(value: &T) {
+    match serde_json::to_string(value) {
+        Ok(json) => eprintln!("{}", json),
+        Err(e) => eprintln!("{}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify() {
+        let io = ReadError::Io(io::Error::new(io::ErrorKind::NotFound, "gone"));
+        assert_eq!(Failure::from(io).status, Status::Io);
+
+        let parse = ReadError::Parse(String::from("Bad level"));
+        assert_eq!(Failure::from(parse).status, Status::Parse);
+
+        let other: Box<dyn Error> = "Something else".into();
+        assert_eq!(Failure::classify(other.as_ref()).status, Status::Error);
+
+        let boxed: Box<dyn Error> = Box::new(Failure::new(Status::Geometry, "Overlaps"));
+        assert_eq!(Failure::classify(boxed.as_ref()).status, Status::Geometry);
+    }
+}
@@ -0,0 +1,81 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Reads NFL cross-sections, splits lines wherever parts overlap so that
+//! they share endpoints, checks the result, and writes it back out as NFL
+//! or as an ANSYS APDL macro.
+//!
+//! ```
+//! use nfl2mac::{Assembly, WriteOptions};
+//!
+//! // A short line on level 2 lying along a longer one on level 1
+//! let text = "PARTNO/'EXAMPLE'\n\
+//!             LEVEL/1\n\
+//!             L00001=LINE/0,0,4,0\n\
+//!             LEVEL/2\n\
+//!             L00002=LINE/1,0,3,0\n\
+//!             FINI/\n";
+//!
+//! let mut asm = Assembly::from_nfl(text).unwrap();
+//! assert!(!asm.validate(0.000001).is_empty());
+//!
+//! // Only the long one needs splitting, at both ends of the short one
+//! let report = asm.split(0.000001);
+//! assert_eq!(report.totals.lines_split, 1);
+//! assert!(asm.validate(0.000001).is_empty());
+//!
+//! let nfl = asm.to_nfl(&WriteOptions::default());
+//! assert!(nfl.contains("LINE/0,0,1,0") && nfl.contains("LINE/3,0,4,0"));
+//! ```
+//!
+//! Assemblies can also be put together in code:
+//!
+//! ```
+//! use euclid::Point2D;
+//! use nfl2mac::{Assembly, WriteOptions};
+//!
+//! let mut asm = Assembly::empty();
+//! asm.add_part(1, vec![("COLOR", "GREEN")])
+//!     .add_line(Point2D::new(0., 0.), Point2D::new(1., 0.))
+//!     .add_circle(Point2D::new(5., 5.), 0.5);
+//!
+//! // Asking for a level that's already there adds to the same part
+//! asm.add_part(1, vec![("FONT", "SOLID")])
+//!     .add_arc(Point2D::new(0., 0.), 1., 0., 90.);
+//! assert_eq!(asm.parts.len(), 1);
+//!
+//! assert_eq!(asm.to_nfl(&WriteOptions::default()),
+//!            "LEVEL/1\nCOLOR/GREEN\nFONT/SOLID\n\
+//!             L00001=LINE/0,0,1,0\n\
+//!             C00002=CIRCLE/CENTER,5,5,RADIUS,0.5\n\
+//!             C00003=CIRCLE/CENTER,0,0,RADIUS,1,ENDANG,90\n\
+//!             FINI/\nSTOP/");
+//! ```
+//!
+//! The command line tool is built on top of this, in the same package.
+
+pub mod assembly;
+
+pub use assembly::{Assembly, ReadError, Margin};
+pub use assembly::part::Part;
+pub use assembly::part::line::Line;
+pub use assembly::part::arc::Arc;
+pub use assembly::part::curve::Curve;
+pub use assembly::nfl::{WriteOptions, NumberFormat, LabelScheme};
+pub use assembly::encoding::{TextFormat, TextEncoding, LineEnding};
+pub use assembly::levels::{LevelFilter, LevelSet, AttrMatch, Remap};
+pub use assembly::report::SplitReport;
+pub use assembly::validate::Problem;
+pub use assembly::diff::{diff, Difference};
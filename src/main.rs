@@ -14,49 +14,92 @@
 // along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
 
 mod args;
-mod assembly;
+mod commands;
+mod batch;
+mod watch;
+mod project;
+mod diag;
 
-use assembly::Assembly;
-use args::{Config, FileType};
+use args::{Config, Command};
+use diag::{Failure, Status};
 
-use std::{fs, error::Error, cmp::min};
-use log::{info, debug};
+use std::{error::Error, cmp::min, panic, process};
+use log::debug;
 use flexi_logger::Logger;
 
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() {
 
-    //let mut opt = Opt::from_args();
-    let conf = Config::new()?;
+    let (mut conf, matches) = Config::from_args();
+    diag::init(conf.format, conf.quiet);
+
+    // Panics would otherwise exit with 101, which scripts don't expect.
+    panic::set_hook(Box::new(|info| {
+        diag::error(None, Status::Error, &format!("nfl2mac crashed: {}", info));
+        diag::finish(Status::Error);
+        process::exit(Status::Error as i32);
+    }));
+
+    if let Err(e) = conf.load_project(&matches) {
+        diag::error(None, Status::Error, &e);
+        diag::finish(Status::Error);
+        process::exit(Status::Error as i32);
+    }
+
+    // The project file can make things quiet.
+    diag::init(conf.format, conf.quiet);
 
 
     let log_levels = ["error", "warn", "info", "debug", "trace"];
     let log_level = log_levels[min(conf.verbose, log_levels.len()-1)];
 
-    if !conf.quiet {
-        Logger::with_env_or_str(log_level)
+    // Errors get reported as diagnostics anyway, so logging would only
+    // get in the way of JSON unless it was asked for.
+    if !conf.quiet && (diag::is_text() || conf.verbose > 0) {
+        let logger = Logger::with_env_or_str(log_level)
             .set_palette(String::from("196;208;-;7;108"))
-            .start()
-            .unwrap();
+            .start();
+
+        if let Err(e) = logger {
+            eprintln!("Could not start logging: {}", e);
+        }
     }
 
-    debug!("{:?}", conf);
-    
-    let mut asm = Assembly::new(&conf.target).unwrap();
-    debug!("File contents:\n{}", asm.to_nfl());
-
-    match conf.target_type.clone().expect("Failed to deduce input type") {
-        FileType::RawNFL => {
-            info!("Raw file... will split and store in {:?}", conf.split_dest());	
-            asm.split(conf.line_precision);
-            debug!("After split:\n{}", asm.to_nfl());
-            fs::write(conf.split_dest(), asm.to_nfl())?;
-        },
-        
-        FileType::SplitNFL => {
-            println!("Already split... not splitting");
-        },
+    match &conf.project {
+        Some(path) => debug!("Using settings from {}", path.display()),
+        None => debug!("No {} found", project::FILE_NAME),
     }
+    debug!("Effective configuration:\n{}", project::describe(&conf));
+
+    let status = match run(&conf) {
+        Ok(()) if diag::warnings() > 0 => Status::Warnings,
+        Ok(()) => Status::Success,
+        Err(e) => {
+            let failure = Failure::classify(e.as_ref());
+            diag::error(None, failure.status, &failure.message);
+            failure.status
+        },
+    };
+
+    diag::finish(status);
+    process::exit(status as i32);
+}
+
+fn run(conf: &Config) -> Result<(), Box<dyn Error>> {
+    match &conf.command {
+        Command::Split { input, output, watch, dry_run } =>
+            commands::split(input, output, *watch, *dry_run),
 
-    Ok(())
+        Command::Convert { input, output, to, from, split, watch } =>
+            commands::convert(input, output, *to, *from, *split, *watch),
+
+        Command::Info { input } =>
+            commands::info(input),
+
+        Command::Check { input } =>
+            commands::check(input),
+
+        Command::Diff { old, new, encoding, line_precision } =>
+            commands::diff(old, new, *encoding, *line_precision),
+    }
 }
@@ -0,0 +1,460 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// Settings that live in an nfl2mac.toml, so that a project gets converted
+// the same way every time without a long command line.
+//
+// The file is looked for next to the first input, then in each directory
+// above it. Anything given on the command line wins over the file.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::collections::BTreeMap;
+use serde::{Serialize, Deserialize};
+use structopt::clap::ArgMatches;
+
+use crate::args::{Config, Command, InputOpts, OutputOpts};
+use nfl2mac::assembly::dxf::LayerLevel;
+
+pub const FILE_NAME: &str = "nfl2mac.toml";
+
+/// Everything that can go in the project file. Each setting has the same
+/// meaning as the command line option of the same name.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Project {
+    /// Same as giving -v this many times
+    pub verbose: Option<usize>,
+    pub quiet: Option<bool>,
+
+    pub input: InputSettings,
+    pub output: OutputSettings,
+    pub numbers: NumberSettings,
+    pub labels: LabelSettings,
+
+    /// Settings for particular levels, keyed by level number
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub levels: BTreeMap<String, LevelSettings>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputSettings {
+    pub glob: Option<String>,
+    pub jobs: Option<usize>,
+    pub target_type: Option<String>,
+    pub encoding: Option<String>,
+    pub line_precision: Option<f64>,
+    pub levels: Option<String>,
+    pub exclude_levels: Option<String>,
+    pub attr: Option<Vec<String>>,
+    pub exclude_attr: Option<Vec<String>>,
+    pub remap: Option<Vec<String>>,
+    pub layer: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputSettings {
+    /// What convert converts to, like --to
+    pub format: Option<String>,
+
+    /// Like --output-dir; relative paths are relative to the project file
+    pub dir: Option<PathBuf>,
+
+    pub force: Option<bool>,
+    pub max_line_len: Option<usize>,
+    pub line_ending: Option<String>,
+    pub eof_marker: Option<bool>,
+    pub split_suffix: Option<String>,
+    pub convert_suffix: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NumberSettings {
+    pub sig_digits: Option<usize>,
+    pub trailing_dot: Option<bool>,
+    pub sci_min_exp: Option<i32>,
+    pub sci_max_exp: Option<i32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LabelSettings {
+    pub scheme: Option<String>,
+    pub width: Option<usize>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LevelSettings {
+    /// Used for overlaps with this level instead of the usual line precision
+    pub line_precision: Option<f64>,
+
+    /// Name of the level's component in APDL macros
+    pub name: Option<String>,
+}
+
+/// Looks for the project file, starting in the directory of `start` (or
+/// `start` itself, if it is a directory) and working up.
+pub fn find(start: &Path) -> Option<PathBuf> {
+    let start = fs::canonicalize(start).ok()?;
+    let dir = if start.is_dir() { start.as_path() } else { start.parent()? };
+
+    dir.ancestors()
+        .map(|d| d.join(FILE_NAME))
+        .find(|p| p.is_file())
+}
+
+impl Project {
+    pub fn load(path: &Path) -> Result<Project, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {:?}: {}", path, e))?;
+        let mut project: Project = toml::from_str(&text)
+            .map_err(|e| format!("Bad project file {:?}: {}", path, e))?;
+
+        if let (Some(dir), Some(base)) = (&mut project.output.dir, path.parent()) {
+            *dir = base.join(&dir);
+        }
+
+        Ok(project)
+    }
+
+    /// Fills in everything that wasn't given on the command line.
+    pub fn apply(&self, conf: &mut Config, matches: &ArgMatches) -> Result<(), String> {
+        if conf.verbose == 0 {
+            conf.verbose = self.verbose.unwrap_or(0);
+        }
+        if !conf.no_quiet {
+            conf.quiet |= self.quiet.unwrap_or(false);
+        }
+
+        // Options with defaults always have a value, so we have to ask
+        // whether they were actually given.
+        let sub = matches.subcommand().1.expect("No subcommand");
+        let given = |name: &str| sub.occurrences_of(name) > 0;
+
+        match &mut conf.command {
+            Command::Split { input, output, .. } => {
+                self.apply_input(input, &given)?;
+                self.apply_output(output, &given)?;
+            },
+
+            Command::Convert { input, output, to, .. } => {
+                self.apply_input(input, &given)?;
+                self.apply_output(output, &given)?;
+
+                if !given("to") {
+                    if let Some(format) = parse("output.format", &self.output.format)? {
+                        *to = format;
+                    }
+                }
+            },
+
+            Command::Info { input } | Command::Check { input } => {
+                self.apply_input(input, &given)?;
+            },
+
+            Command::Diff { encoding, line_precision, .. } => {
+                if encoding.is_none() {
+                    *encoding = parse("input.encoding", &self.input.encoding)?;
+                }
+                fill(line_precision, given("line-precision"), &self.input.line_precision);
+            },
+        }
+
+        Ok(())
+    }
+
+    fn apply_input(&self, input: &mut InputOpts, given: &dyn Fn(&str) -> bool)
+        -> Result<(), String>
+    {
+        let s = &self.input;
+
+        if input.glob.is_none() {
+            input.glob = s.glob.clone();
+        }
+        fill(&mut input.line_precision, given("line-precision"), &s.line_precision);
+        input.jobs = input.jobs.or(s.jobs);
+
+        if input.target_type.is_none() {
+            input.target_type = parse("input.target_type", &s.target_type)?;
+        }
+        if input.encoding.is_none() {
+            input.encoding = parse("input.encoding", &s.encoding)?;
+        }
+        if input.levels.is_none() {
+            input.levels = parse("input.levels", &s.levels)?;
+        }
+        if input.exclude_levels.is_none() {
+            input.exclude_levels = parse("input.exclude_levels", &s.exclude_levels)?;
+        }
+        if input.attr.is_empty() {
+            input.attr = parse_all("input.attr", &s.attr)?;
+        }
+        if input.exclude_attr.is_empty() {
+            input.exclude_attr = parse_all("input.exclude_attr", &s.exclude_attr)?;
+        }
+        if input.remap.is_empty() {
+            input.remap = parse_all("input.remap", &s.remap)?;
+        }
+        if input.layer.is_empty() {
+            input.layer = parse_all("input.layer", &s.layer)?;
+        }
+
+        for (level, settings) in self.levels()? {
+            if let Some(p) = settings.line_precision {
+                input.level_precision.insert(level, p);
+            }
+
+            // Named levels take the DXF layers of the same name
+            if let Some(name) = &settings.name {
+                if !input.layer.iter().any(|l| l.layer.eq_ignore_ascii_case(name)) {
+                    input.layer.push(LayerLevel { layer: name.clone(), level });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_output(&self, output: &mut OutputOpts, given: &dyn Fn(&str) -> bool)
+        -> Result<(), String>
+    {
+        let s = &self.output;
+
+        if output.output.is_none() && output.output_dir.is_none() {
+            output.output_dir = s.dir.clone();
+        }
+        if !output.no_force {
+            output.force |= s.force.unwrap_or(false);
+        }
+
+        fill(&mut output.max_line_len, given("max-line-len"), &s.max_line_len);
+        fill(&mut output.split_suffix, given("split-suffix"), &s.split_suffix);
+        fill(&mut output.convert_suffix, given("convert-suffix"), &s.convert_suffix);
+
+        if output.line_ending.is_none() {
+            output.line_ending = parse("output.line_ending", &s.line_ending)?;
+        }
+
+        if !output.eof_marker && !output.no_eof_marker {
+            match s.eof_marker {
+                Some(true) => output.eof_marker = true,
+                Some(false) => output.no_eof_marker = true,
+                None => {},
+            }
+        }
+
+        let n = &self.numbers;
+        output.sig_digits = output.sig_digits.or(n.sig_digits);
+        if !output.no_trailing_dot {
+            output.trailing_dot |= n.trailing_dot.unwrap_or(false);
+        }
+        output.sci_min_exp = output.sci_min_exp.or(n.sci_min_exp);
+        output.sci_max_exp = output.sci_max_exp.or(n.sci_max_exp);
+
+        if output.label_scheme.is_none() {
+            output.label_scheme = parse("labels.scheme", &self.labels.scheme)?;
+        }
+        output.label_width = output.label_width.or(self.labels.width);
+
+        for (level, settings) in self.levels()? {
+            if let Some(name) = &settings.name {
+                output.level_names.insert(level, name.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn levels(&self) -> Result<Vec<(u64, &LevelSettings)>, String> {
+        self.levels.iter()
+            .map(|(k, v)| match k.parse() {
+                Ok(level) => Ok((level, v)),
+                Err(_) => Err(format!("Bad level {:?} in {}", k, FILE_NAME)),
+            })
+            .collect()
+    }
+}
+
+/// Uses the value from the file, unless one was given on the command line.
+fn fill<T: Clone>(field: &mut T, given: bool, value: &Option<T>) {
+    if let (false, Some(v)) = (given, value) {
+        *field = v.clone();
+    }
+}
+
+fn parse<T: FromStr>(key: &str, value: &Option<String>) -> Result<Option<T>, String> {
+    match value {
+        Some(v) => v.parse()
+            .map(Some)
+            .map_err(|_| format!("Bad {} {:?} in {}", key, v, FILE_NAME)),
+        None => Ok(None),
+    }
+}
+
+fn parse_all<T: FromStr>(key: &str, values: &Option<Vec<String>>) -> Result<Vec<T>, String> {
+    values.iter().flatten()
+        .map(|v| v.parse().map_err(|_| format!("Bad {} {:?} in {}", key, v, FILE_NAME)))
+        .collect()
+}
+
+/// Writes out the settings that are actually in use, in the same form as
+/// the project file.
+pub fn describe(conf: &Config) -> String {
+    let mut project = Project {
+        verbose: Some(conf.verbose),
+        quiet: Some(conf.quiet),
+        ..Project::default()
+    };
+
+    match &conf.command {
+        Command::Split { input, output, .. } => {
+            project.describe_input(input);
+            project.describe_output(output);
+        },
+
+        Command::Convert { input, output, to, .. } => {
+            project.describe_input(input);
+            project.describe_output(output);
+            project.output.format = Some(to.to_string());
+        },
+
+        Command::Info { input } | Command::Check { input } => {
+            project.describe_input(input);
+        },
+
+        Command::Diff { encoding, line_precision, .. } => {
+            project.input.encoding = encoding.map(|e| e.to_string());
+            project.input.line_precision = Some(*line_precision);
+        },
+    }
+
+    toml::to_string(&project).unwrap_or_else(|e| e.to_string())
+}
+
+fn describe_all<T: ToString>(values: &[T]) -> Option<Vec<String>> {
+    if values.is_empty() {
+        None
+    }
+    else {
+        Some(values.iter().map(T::to_string).collect())
+    }
+}
+
+impl Project {
+    fn describe_input(&mut self, input: &InputOpts) {
+        self.input = InputSettings {
+            glob: input.glob.clone(),
+            jobs: input.jobs,
+            target_type: input.target_type.as_ref().map(|t| t.to_string()),
+            encoding: input.encoding.map(|e| e.to_string()),
+            line_precision: Some(input.line_precision),
+            levels: input.levels.as_ref().map(|l| l.to_string()),
+            exclude_levels: input.exclude_levels.as_ref().map(|l| l.to_string()),
+            attr: describe_all(&input.attr),
+            exclude_attr: describe_all(&input.exclude_attr),
+            remap: describe_all(&input.remap),
+            layer: describe_all(&input.layer),
+        };
+
+        for (level, &p) in &input.level_precision {
+            self.levels.entry(level.to_string()).or_default().line_precision = Some(p);
+        }
+    }
+
+    fn describe_output(&mut self, output: &OutputOpts) {
+        self.output = OutputSettings {
+            format: None,
+            dir: output.output_dir.clone(),
+            force: Some(output.force),
+            max_line_len: Some(output.max_line_len),
+            line_ending: output.line_ending.map(|l| l.to_string()),
+            eof_marker: match (output.eof_marker, output.no_eof_marker) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            },
+            split_suffix: Some(output.split_suffix.clone()),
+            convert_suffix: Some(output.convert_suffix.clone()),
+        };
+
+        self.numbers = NumberSettings {
+            sig_digits: output.sig_digits,
+            trailing_dot: Some(output.trailing_dot),
+            sci_min_exp: output.sci_min_exp,
+            sci_max_exp: output.sci_max_exp,
+        };
+
+        self.labels = LabelSettings {
+            scheme: output.label_scheme.map(|s| s.to_string()),
+            width: output.label_width,
+        };
+
+        for (level, name) in &output.level_names {
+            self.levels.entry(level.to_string()).or_default().name = Some(name.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels() {
+        let project: Project = toml::from_str(
+            "[input]\nline_precision = 1e-5\n\n[levels.20]\nname = \"STATOR\"\n"
+        ).unwrap();
+
+        assert_eq!(project.input.line_precision, Some(1e-5));
+
+        let levels = project.levels().unwrap();
+        assert_eq!(levels.len(), 1);
+        assert_eq!(levels[0].0, 20);
+        assert_eq!(levels[0].1.name.as_deref(), Some("STATOR"));
+
+        let bad: Project = toml::from_str("[levels.x]\n").unwrap();
+        assert!(bad.levels().is_err());
+        assert!(toml::from_str::<Project>("colour = 1\n").is_err());
+    }
+
+    #[test]
+    fn flags_override_settings() {
+        use structopt::StructOpt;
+
+        let project: Project = toml::from_str(
+            "quiet = true\n\n[output]\nforce = true\n\n[numbers]\ntrailing_dot = true\n"
+        ).unwrap();
+
+        let output = |args: &[&str]| {
+            let matches = Config::clap().get_matches_from(args);
+            let mut conf = Config::from_clap(&matches);
+            project.apply(&mut conf, &matches).unwrap();
+
+            match conf.command {
+                Command::Split { output, .. } => (conf.quiet, output.force, output.trailing_dot),
+                _ => unreachable!(),
+            }
+        };
+
+        assert_eq!(output(&["nfl2mac", "split", "a.nfl"]), (true, true, true));
+        assert_eq!(output(&["nfl2mac", "split", "a.nfl", "--no-quiet", "--no-force", "--no-trailing-dot"]),
+                   (false, false, false));
+    }
+}
@@ -0,0 +1,172 @@
+// This file is part of nfl2mac.
+// 
+// nfl2mac is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// nfl2mac is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with nfl2mac.  If not, see <https://www.gnu.org/licenses/>.
+
+// Keeps a command running, redoing its work whenever an input file changes.
+//
+// CAD tools tend to write their exports in several pieces, so nothing is
+// done until the file has been left alone for a little while. After each
+// run, the differences from the last result are printed.
+
+use std::{fs, env};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use log::{info, warn, error, debug};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use crate::args::{InputOpts, OutputOpts};
+use nfl2mac::assembly::{Assembly, diff};
+use crate::batch::{self, DefaultDest, FileReport, Work};
+
+/// How long a file has to go unchanged before it's read again
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Differences beyond this many aren't printed, just counted
+const MAX_DIFF_LINES: usize = 10;
+
+/// Runs `work` on every input file, then again on each one that changes.
+/// The first run only overwrites existing outputs if --force was given;
+/// after that, outputs this has written are overwritten as if it was.
+/// This only returns if something goes wrong with the watching itself.
+pub fn run<D: DefaultDest, F: Work>(input: &InputOpts, output: &OutputOpts, dest: D, work: F)
+    -> Result<(), Box<dyn Error>>
+{
+    let forced = OutputOpts { force: true, ..output.clone() };
+
+    let reports = batch::process(input, output, &dest, &work)?;
+    if let Err(e) = batch::summarize(&reports) {
+        error!("{}", e);
+    }
+
+    let mut results = HashMap::new();
+    let mut written = HashSet::new();
+    for report in reports {
+        remember(report, &mut results, &mut written);
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+
+    for target in &input.targets {
+        let target = fs::canonicalize(target)?;
+
+        // Files get watched through their directory, since saving often
+        // means replacing the file with a new one.
+        if target.is_dir() {
+            watcher.watch(&target, RecursiveMode::Recursive)?;
+        }
+        else {
+            let dir = target.parent().ok_or("Bad path")?;
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+    }
+
+    println!("Watching for changes; press Ctrl+C to stop");
+
+    loop {
+        let mut changed = BTreeSet::new();
+
+        // Wait for something to happen, then for things to settle down
+        collect(rx.recv()?, &mut changed);
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            collect(event, &mut changed);
+        }
+
+        for path in changed {
+            if written.contains(&path) || !path.is_file() || !input.includes(&path) {
+                continue;
+            }
+
+            let target = relative(&path);
+            info!("{} changed", target.display());
+
+            // Only what this has written itself is fair game
+            let output = if results.contains_key(&path) { &forced } else { output };
+
+            let mut report = FileReport::new(&target);
+            if let Err(e) = work(&target, output, &mut report) {
+                error!("{}: {}", target.display(), e);
+                continue;
+            }
+
+            for w in &report.warnings {
+                warn!("{}: {}", target.display(), w);
+            }
+
+            match (results.get(&path), &report.result) {
+                (Some(old), Some(new)) => print_diff(&target, old, new, input.line_precision),
+                (None, Some(_)) => println!("{}: written for the first time", target.display()),
+                _ => {},
+            }
+
+            remember(report, &mut results, &mut written);
+        }
+    }
+}
+
+/// Keeps track of what a run produced, by the canonical path of its input.
+fn remember(report: FileReport, results: &mut HashMap<PathBuf, Assembly>,
+            written: &mut HashSet<PathBuf>)
+{
+    if let Some(w) = report.written.as_ref().and_then(|w| fs::canonicalize(w).ok()) {
+        written.insert(w);
+    }
+
+    if let (Ok(path), Some(result)) = (fs::canonicalize(&report.path), report.result) {
+        results.insert(path, result);
+    }
+}
+
+fn collect(event: notify::Result<Event>, changed: &mut BTreeSet<PathBuf>) {
+    match event {
+        Ok(event) => {
+            debug!("{:?}", event);
+
+            if let EventKind::Create(_) | EventKind::Modify(_) = event.kind {
+                changed.extend(event.paths);
+            }
+        },
+
+        Err(e) => warn!("Error while watching: {}", e),
+    }
+}
+
+/// Makes the path relative to the current directory, if it's in there,
+/// so it's shorter to print and ends up where the user would expect.
+fn relative(path: &Path) -> PathBuf {
+    env::current_dir().ok()
+        .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+fn print_diff(target: &Path, old: &Assembly, new: &Assembly, max_dist: f64) {
+    let differences = diff::diff(old, new, max_dist);
+
+    if differences.is_empty() {
+        println!("{}: no changes in the output", target.display());
+        return;
+    }
+
+    println!("{}: {} change(s) in the output", target.display(), differences.len());
+    for d in differences.iter().take(MAX_DIFF_LINES) {
+        println!("  {}", d);
+    }
+
+    if differences.len() > MAX_DIFF_LINES {
+        println!("  ... and {} more", differences.len() - MAX_DIFF_LINES);
+    }
+}